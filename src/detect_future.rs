@@ -0,0 +1,124 @@
+//! A future which runs a blocking detection on a thread of its own, so that async callers
+//! don't stall their runtime while a device is mounted and probed. Enabled by the `async`
+//! feature.
+//!
+//! No runtime is depended upon: the worker wakes the task that last polled the future once
+//! it has finished, which works with any executor.
+
+use std::any::Any;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use {DetectError, OS};
+
+type Outcome = Result<Result<Option<OS>, DetectError>, Box<dyn Any + Send>>;
+
+#[derive(Default)]
+struct Shared {
+    outcome: Option<Outcome>,
+    waker: Option<Waker>,
+}
+
+/// The result of `detect_os_from_device_async` or `Detector::detect_async`, which resolves
+/// to the same result as their blocking equivalents.
+///
+/// The probe runs to completion on its worker thread even if this is dropped, so that the
+/// device is always unmounted.
+#[must_use = "futures do nothing unless polled"]
+pub struct DetectFuture {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl DetectFuture {
+    /// Starts running `probe` on a worker thread. A panic within it is resumed on the task
+    /// which polls the future.
+    pub(crate) fn spawn<F>(probe: F) -> Self
+    where
+        F: FnOnce() -> Result<Option<OS>, DetectError> + Send + 'static,
+    {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let worker = shared.clone();
+        thread::spawn(move || {
+            let outcome = panic::catch_unwind(AssertUnwindSafe(probe));
+            let mut shared = worker.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            shared.outcome = Some(outcome);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        });
+
+        DetectFuture { shared }
+    }
+}
+
+impl Future for DetectFuture {
+    type Output = Result<Option<OS>, DetectError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match shared.outcome.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(why)) => panic::resume_unwind(why),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::task::Wake;
+    use std::thread::Thread;
+    use std::time::Duration;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls a future on the current thread until it's ready, parking between polls.
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn wakes_once_finished() {
+        let (tx, rx) = mpsc::channel::<()>();
+        let future = DetectFuture::spawn(move || {
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            Ok(None)
+        });
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send(()).unwrap();
+        });
+
+        assert!(block_on(future).unwrap().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "probe panicked")]
+    fn resumes_panics() {
+        let _ = block_on(DetectFuture::spawn(|| panic!("probe panicked")));
+    }
+}
@@ -0,0 +1,117 @@
+//! Hints about the contents of GPT partitions, from their partition type GUIDs.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// What a partition is expected to hold, according to its GPT partition type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExpectedOs {
+    /// A Windows data, recovery, or reserved partition.
+    Windows,
+    /// A Linux root, home, or data partition, or a LVM or LUKS volume.
+    Linux,
+    /// An APFS or HFS+ volume.
+    MacOs,
+    /// A ChromeOS kernel or root partition.
+    ChromeOs,
+    /// A FreeBSD boot, UFS, or ZFS partition.
+    FreeBsd,
+    /// An EFI system partition, which holds bootloaders rather than an OS.
+    ///
+    /// See `detect_bootloader` to find out which.
+    EfiSystem,
+    /// A Linux swap partition, which never holds an OS.
+    Swap,
+}
+
+/// The known partition type GUIDs, in upper case.
+const GPT_TYPES: &[(&str, ExpectedOs)] = &[
+    ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B", ExpectedOs::EfiSystem),
+    // Basic data, reserved, and recovery partitions.
+    ("EBD0A0A2-B9E5-4433-87C0-68B6B72699C7", ExpectedOs::Windows),
+    ("E3C9E316-0B5C-4DB8-817D-F92DF00215AE", ExpectedOs::Windows),
+    ("DE94BBA4-06D1-4D40-A16A-BFD50179D6AC", ExpectedOs::Windows),
+    // Filesystem data, x86-64 and AArch64 roots, home, server data, LVM, and LUKS.
+    ("0FC63DAF-8483-4772-8E79-3D69D8477DE4", ExpectedOs::Linux),
+    ("4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709", ExpectedOs::Linux),
+    ("B921B045-1DF0-41C3-AF44-4C6F280D3FAE", ExpectedOs::Linux),
+    ("933AC7E1-2EB4-4F13-B844-0E14E2AEF915", ExpectedOs::Linux),
+    ("3B8F8425-20E0-4F3B-907F-1A25A76F98E8", ExpectedOs::Linux),
+    ("E6D6D379-F507-44C2-A23C-238F2A3DF928", ExpectedOs::Linux),
+    ("CA7D7CCB-63ED-4C53-861C-1742536059CC", ExpectedOs::Linux),
+    ("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F", ExpectedOs::Swap),
+    // APFS and HFS+.
+    ("7C3457EF-0000-11AA-AA11-00306543ECAC", ExpectedOs::MacOs),
+    ("48465300-0000-11AA-AA11-00306543ECAC", ExpectedOs::MacOs),
+    // Kernel and root partitions.
+    ("FE3A2A5D-4F32-41A7-B725-ACCC3285A309", ExpectedOs::ChromeOs),
+    ("3CB8E202-3B7E-47DD-8A3C-7FF2A13CFCEC", ExpectedOs::ChromeOs),
+    // Boot, UFS, and ZFS partitions.
+    ("83BD6B9D-7F41-11DC-BE0B-001560B84F0F", ExpectedOs::FreeBsd),
+    ("516E7CB6-6ECF-11D6-8FF8-00022D09712B", ExpectedOs::FreeBsd),
+    ("516E7CBA-6ECF-11D6-8FF8-00022D09712B", ExpectedOs::FreeBsd),
+];
+
+/// Classifies a partition by its GPT partition type GUID, such as the `ID_PART_ENTRY_TYPE`
+/// reported by udev, so that callers can prioritize detectors or skip partitions that
+/// can't hold an OS.
+///
+/// The GUID is matched case-insensitively, and may be wrapped in braces. Those handled are:
+///
+/// - EFI system partition: `C12A7328-F81F-11D2-BA4B-00A0C93EC93B`
+/// - Microsoft basic data: `EBD0A0A2-B9E5-4433-87C0-68B6B72699C7`
+/// - Microsoft reserved: `E3C9E316-0B5C-4DB8-817D-F92DF00215AE`
+/// - Windows recovery environment: `DE94BBA4-06D1-4D40-A16A-BFD50179D6AC`
+/// - Linux filesystem data: `0FC63DAF-8483-4772-8E79-3D69D8477DE4`
+/// - Linux x86-64 root: `4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709`
+/// - Linux AArch64 root: `B921B045-1DF0-41C3-AF44-4C6F280D3FAE`
+/// - Linux home: `933AC7E1-2EB4-4F13-B844-0E14E2AEF915`
+/// - Linux server data: `3B8F8425-20E0-4F3B-907F-1A25A76F98E8`
+/// - Linux LVM: `E6D6D379-F507-44C2-A23C-238F2A3DF928`
+/// - Linux LUKS: `CA7D7CCB-63ED-4C53-861C-1742536059CC`
+/// - Linux swap: `0657FD6D-A4AB-43C4-84E5-0933C84B4F4F`
+/// - Apple APFS: `7C3457EF-0000-11AA-AA11-00306543ECAC`
+/// - Apple HFS+: `48465300-0000-11AA-AA11-00306543ECAC`
+/// - ChromeOS kernel: `FE3A2A5D-4F32-41A7-B725-ACCC3285A309`
+/// - ChromeOS root: `3CB8E202-3B7E-47DD-8A3C-7FF2A13CFCEC`
+/// - FreeBSD boot: `83BD6B9D-7F41-11DC-BE0B-001560B84F0F`
+/// - FreeBSD UFS: `516E7CB6-6ECF-11D6-8FF8-00022D09712B`
+/// - FreeBSD ZFS: `516E7CBA-6ECF-11D6-8FF8-00022D09712B`
+pub fn classify_by_gpt_type(guid: &str) -> Option<ExpectedOs> {
+    let guid = guid.trim().trim_start_matches('{').trim_end_matches('}');
+    GPT_TYPES
+        .iter()
+        .find(|&&(known, _)| known.eq_ignore_ascii_case(guid))
+        .map(|&(_, expected)| expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_types() {
+        let cases = [
+            ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B", ExpectedOs::EfiSystem),
+            ("EBD0A0A2-B9E5-4433-87C0-68B6B72699C7", ExpectedOs::Windows),
+            ("0FC63DAF-8483-4772-8E79-3D69D8477DE4", ExpectedOs::Linux),
+            ("7C3457EF-0000-11AA-AA11-00306543ECAC", ExpectedOs::MacOs),
+            ("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F", ExpectedOs::Swap),
+        ];
+
+        for &(guid, expected) in &cases {
+            assert_eq!(classify_by_gpt_type(guid), Some(expected));
+        }
+    }
+
+    #[test]
+    fn guid_formatting() {
+        let efi = Some(ExpectedOs::EfiSystem);
+        assert_eq!(classify_by_gpt_type("c12a7328-f81f-11d2-ba4b-00a0c93ec93b"), efi);
+        assert_eq!(classify_by_gpt_type("{C12A7328-F81F-11D2-BA4B-00A0C93EC93B}"), efi);
+        assert_eq!(classify_by_gpt_type(" C12A7328-F81F-11D2-BA4B-00A0C93EC93B\n"), efi);
+        assert_eq!(classify_by_gpt_type("00000000-0000-0000-0000-000000000000"), None);
+        assert_eq!(classify_by_gpt_type("C12A7328"), None);
+    }
+}
@@ -0,0 +1,256 @@
+//! Reads the menu entries of a GRUB configuration, and which of them boots by default.
+//!
+//! Only what `grub-mkconfig` generates is understood: `menuentry` and `submenu` blocks
+//! with one per line, and `set default=` lines outside of them. Scripts aren't evaluated,
+//! except that a default of `${saved_entry}` is looked up in the `grubenv`.
+
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where the configuration is found within `/boot`: `grub2` on Fedora and openSUSE.
+const GRUB_DIRS: &[&str] = &["grub", "grub2"];
+
+/// A one-time default, set by `grub-reboot`, which isn't the user's preference.
+const NEXT_ENTRY: &str = "${next_entry}";
+const SAVED_ENTRY: &str = "${saved_entry}";
+
+/// The menu of a GRUB configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GrubMenu {
+    /// Every menu entry, in order, including those within submenus.
+    pub entries: Vec<GrubEntry>,
+    /// The `default` setting, with a saved default replaced by the `saved_entry` of the
+    /// `grubenv`. It may be an index such as `0` or `1>2`, a title, or an ID.
+    pub default: Option<String>,
+}
+
+/// A `menuentry` of a GRUB configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GrubEntry {
+    /// The title of the entry, prefixed with that of each submenu it's within and `>`, as
+    /// in `Advanced options for Ubuntu>Ubuntu, with Linux 6.2.0-39-generic`.
+    pub title: String,
+    /// The position of the entry, and each submenu it's within, from 0.
+    pub index: Vec<usize>,
+    /// The IDs of the entry and each submenu it's within, from `--id` or
+    /// `$menuentry_id_option`.
+    pub ids: Vec<Option<String>>,
+}
+
+impl GrubMenu {
+    /// The entry that boots by default, if the `default` setting names one.
+    pub fn default_entry(&self) -> Option<&GrubEntry> {
+        let default = self.default.as_ref()?;
+        self.entries.iter().find(|entry| entry.is_named_by(default))
+    }
+}
+
+impl GrubEntry {
+    /// Whether the entry is named by a `default` setting, in which each level of
+    /// submenus may be given by its index, title, or ID.
+    fn is_named_by(&self, default: &str) -> bool {
+        let titles: Vec<&str> = self.title.split('>').collect();
+        let names: Vec<&str> = default.split('>').collect();
+        names.len() == self.index.len()
+            && names.iter().enumerate().all(|(level, &name)| {
+                name.parse() == Ok(self.index[level])
+                    || titles.get(level) == Some(&name)
+                    || self.ids[level].as_deref() == Some(name)
+            })
+    }
+}
+
+/// Reads the GRUB menu configured in a `/boot` directory, or in a separate `/boot`
+/// partition mounted at the given path.
+pub fn read_grub_menu(boot: &Path) -> Option<GrubMenu> {
+    GRUB_DIRS.iter().find_map(|dir| {
+        let dir = boot.join(dir);
+        let config = fs::read_to_string(dir.join("grub.cfg")).ok()?;
+        let mut menu = parse_grub_config(&config);
+        if menu.default.as_deref() == Some(SAVED_ENTRY) {
+            menu.default =
+                fs::read_to_string(dir.join("grubenv")).ok().and_then(|env| saved_entry(&env));
+        }
+
+        Some(menu)
+    })
+}
+
+/// Parses the menu entries and default of a `grub.cfg`, without reading the `grubenv`.
+pub fn parse_grub_config(config: &str) -> GrubMenu {
+    let mut menu = GrubMenu::default();
+    // The submenus that the current line is within, and blocks of any other kind, which
+    // are `None`; along with how many entries precede the next at this level.
+    let mut blocks: Vec<Option<(String, usize, Option<String>)>> = Vec::new();
+    let mut position = 0;
+
+    for line in config.lines() {
+        let words = split_words(line);
+        let opens = words.last().map(String::as_str) == Some("{");
+        match words.first().map(String::as_str) {
+            Some("}") => {
+                if let Some(Some((_, index, _))) = blocks.pop() {
+                    position = index + 1;
+                }
+            }
+            Some(kind @ "menuentry") | Some(kind @ "submenu") if words.len() > 1 => {
+                let id = entry_id(&words[2..]);
+                if kind == "submenu" {
+                    blocks.push(Some((words[1].clone(), position, id)));
+                    position = 0;
+                    continue;
+                }
+
+                let submenus = blocks.iter().flatten();
+                let mut title: Vec<&str> = submenus.clone().map(|s| s.0.as_str()).collect();
+                let mut index: Vec<usize> = submenus.clone().map(|s| s.1).collect();
+                let mut ids: Vec<Option<String>> = submenus.map(|s| s.2.clone()).collect();
+                title.push(&words[1]);
+                index.push(position);
+                ids.push(id);
+
+                menu.entries.push(GrubEntry { title: title.join(">"), index, ids });
+                position += 1;
+                if opens {
+                    blocks.push(None);
+                }
+            }
+            Some("set") if blocks.is_empty() => {
+                let default = words.get(1).and_then(|word| word.strip_prefix("default="));
+                if let Some(default) = default.filter(|&default| default != NEXT_ENTRY) {
+                    menu.default = Some(default.to_owned());
+                }
+            }
+            _ if opens => blocks.push(None),
+            _ => (),
+        }
+    }
+
+    menu
+}
+
+/// Reads the `saved_entry` of a `grubenv`, which is padded to its block with `#`.
+fn saved_entry(env: &str) -> Option<String> {
+    env.lines()
+        .find_map(|line| line.strip_prefix("saved_entry="))
+        .filter(|entry| !entry.is_empty())
+        .map(String::from)
+}
+
+/// Finds the ID among the options of a `menuentry` or `submenu`.
+fn entry_id(options: &[String]) -> Option<String> {
+    options.iter().enumerate().find_map(|(position, option)| match option.as_str() {
+        "--id" | "$menuentry_id_option" => options.get(position + 1).cloned(),
+        option => option.strip_prefix("--id=").map(String::from),
+    })
+}
+
+/// Splits a line of a GRUB script into words, as its shell-like syntax would, removing
+/// quotes and escapes, and stopping at a comment.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' if word.is_none() => break,
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let quoted = chars.by_ref().take_while(|&c| c != '\'');
+                word.get_or_insert_with(String::new).extend(quoted);
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    words.extend(word);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UBUNTU_GRUB_CFG: &str = include_str!("../tests/fixtures/dual-boot/boot/grub/grub.cfg");
+
+    fn titles(menu: &GrubMenu) -> Vec<&str> {
+        menu.entries.iter().map(|entry| entry.title.as_str()).collect()
+    }
+
+    #[test]
+    fn ubuntu_menu() {
+        let menu = parse_grub_config(UBUNTU_GRUB_CFG);
+        assert_eq!(
+            titles(&menu),
+            [
+                "Ubuntu",
+                "Advanced options for Ubuntu>Ubuntu, with Linux 6.2.0-39-generic",
+                "Advanced options for Ubuntu>Ubuntu, with Linux 6.2.0-39-generic (recovery mode)",
+                "Advanced options for Ubuntu>Ubuntu, with Linux 6.2.0-37-generic",
+                "Windows Boot Manager (on /dev/nvme0n1p1)",
+                "UEFI Firmware Settings",
+            ]
+        );
+
+        assert_eq!(menu.entries[3].index, [1, 2]);
+        assert_eq!(menu.entries[4].index, [2]);
+        assert_eq!(menu.default.as_deref(), Some("0"));
+        assert_eq!(menu.default_entry().map(|entry| entry.title.as_str()), Some("Ubuntu"));
+    }
+
+    #[test]
+    fn default_entries() {
+        let menu = parse_grub_config(UBUNTU_GRUB_CFG);
+        let default = |default: &str| {
+            let menu = GrubMenu { default: Some(default.into()), ..menu.clone() };
+            menu.default_entry().map(|entry| entry.title.clone())
+        };
+
+        let advanced = "Advanced options for Ubuntu";
+        let older = "Advanced options for Ubuntu>Ubuntu, with Linux 6.2.0-37-generic";
+        assert_eq!(default("1>2").as_deref(), Some(older));
+        assert_eq!(default(older).as_deref(), Some(older));
+        assert_eq!(default(&format!("{}>2", advanced)).as_deref(), Some(older));
+        let by_id = "gnulinux-advanced-8d6c2f3e>gnulinux-6.2.0-37-generic-advanced-8d6c2f3e";
+        assert_eq!(default(by_id).as_deref(), Some(older));
+        assert_eq!(default("2").as_deref(), Some("Windows Boot Manager (on /dev/nvme0n1p1)"));
+        assert_eq!(default("1"), None);
+        assert_eq!(default("7"), None);
+    }
+
+    #[test]
+    fn saved_default() {
+        let config = "if [ \"${next_entry}\" ] ; then\n   set default=\"${next_entry}\"\nelse\n   \
+                      set default=\"${saved_entry}\"\nfi\nmenuentry 'Fedora Linux' {\n}\n";
+        assert_eq!(parse_grub_config(config).default.as_deref(), Some(SAVED_ENTRY));
+
+        let env = "# GRUB Environment Block\nsaved_entry=Fedora Linux\n#################\n";
+        assert_eq!(saved_entry(env).as_deref(), Some("Fedora Linux"));
+        assert_eq!(saved_entry("# GRUB Environment Block\nsaved_entry=\n"), None);
+    }
+
+    #[test]
+    fn shell_words() {
+        let words = split_words("menuentry 'It'\\''s' --class os {");
+        assert_eq!(words, ["menuentry", "It's", "--class", "os", "{"]);
+        let words = split_words("  set default=\"a \\\"b\\\"\" # comment");
+        assert_eq!(words, ["set", "default=a \"b\""]);
+        assert_eq!(split_words("# set default=1"), Vec::<String>::new());
+    }
+}
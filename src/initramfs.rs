@@ -0,0 +1,246 @@
+//! Extracts the os-release embedded in an initramfs, as a last resort for installs which
+//! have lost their own. Enabled by the `initramfs` feature.
+//!
+//! An initramfs is a series of newc cpio archives, each of which may be compressed, such as
+//! an uncompressed archive of early microcode followed by the gzip or zstd compressed
+//! root of the initramfs.
+
+use flate2::read::GzDecoder;
+use ruzstd::StreamingDecoder;
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use {compare_versions, read_dir_names};
+
+const CPIO_MAGIC: &[u8] = b"07070";
+const CPIO_HEADER_LEN: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+
+/// Paths of the os-release within the archive, in order of precedence. Those of dracut
+/// describe the initramfs itself, but still name the distribution that built it.
+const OS_RELEASE_PATHS: &[&str] =
+    &["etc/os-release", "usr/lib/os-release", "etc/initrd-release", "usr/lib/initrd-release"];
+
+/// Larger files aren't plausibly an os-release, and aren't read into memory.
+const OS_RELEASE_MAX_LEN: u64 = 64 * 1024;
+/// Names longer than `PATH_MAX` can't be unpacked, and mean the archive is corrupt.
+const MAX_NAME_LEN: usize = 4096;
+
+/// Finds the initramfs images in `/boot`, with the newest kernel's first.
+pub fn find_images(base: &Path) -> Vec<PathBuf> {
+    const PREFIXES: &[&str] = &["initrd.img-", "initramfs-", "initrd-"];
+    let boot = base.join("boot");
+    let mut images: Vec<String> = read_dir_names(&boot)
+        .filter(|name| PREFIXES.iter().any(|prefix| name.starts_with(prefix)))
+        .collect();
+
+    images.sort_by(|a, b| compare_versions(b, a));
+    images.into_iter().map(|name| boot.join(name)).collect()
+}
+
+/// Reads the contents of the os-release within an initramfs image.
+pub fn extract_os_release<R: Read>(mut image: R) -> io::Result<Option<Vec<u8>>> {
+    let mut found = BTreeMap::new();
+    loop {
+        // Archives are padded with zeros to a block boundary before the next begins.
+        let mut magic = [0; 6];
+        match image.read(&mut magic[..1])? {
+            0 => break,
+            _ if magic[0] == 0 => continue,
+            _ => (),
+        }
+
+        match image.read_exact(&mut magic[1..]) {
+            Ok(()) => (),
+            Err(ref why) if why.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(why) => return Err(why),
+        }
+
+        let archive = Cursor::new(magic).chain(&mut image);
+        if magic.starts_with(CPIO_MAGIC) {
+            read_cpio(archive, &mut found)?;
+            continue;
+        }
+
+        // A compressed archive runs to the end of the image.
+        if magic.starts_with(GZIP_MAGIC) {
+            read_cpio(GzDecoder::new(archive), &mut found)?;
+        } else if magic.starts_with(ZSTD_MAGIC) {
+            let decoder = StreamingDecoder::new(archive)
+                .map_err(|why| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", why)))?;
+            read_cpio(decoder, &mut found)?;
+        } else {
+            debug!("unsupported initramfs compression with magic {:02x?}", magic);
+        }
+
+        break;
+    }
+
+    Ok(OS_RELEASE_PATHS.iter().find_map(|path| found.remove(path)))
+}
+
+/// Reads the entries of a newc cpio archive up to its trailer, keeping the contents of
+/// any os-release files. Later entries replace earlier ones, as when unpacked.
+fn read_cpio<R: Read>(
+    mut archive: R,
+    found: &mut BTreeMap<&'static str, Vec<u8>>,
+) -> io::Result<()> {
+    loop {
+        let mut header = [0; CPIO_HEADER_LEN];
+        archive.read_exact(&mut header)?;
+        if !header.starts_with(CPIO_MAGIC) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a newc cpio archive"));
+        }
+
+        let field = |index: usize| {
+            let hex = &header[6 + index * 8..14 + index * 8];
+            std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid cpio header"))
+        };
+
+        let mode = field(1)?;
+        let size = u64::from(field(6)?);
+        let name_len = field(11)? as usize;
+        if name_len > MAX_NAME_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "cpio entry name is too long"));
+        }
+
+        let mut name = vec![0; name_len];
+        archive.read_exact(&mut name)?;
+        skip(&mut archive, padding((CPIO_HEADER_LEN + name_len) as u64))?;
+
+        // The name is terminated by a NUL, and may be prefixed with `./`.
+        let name = String::from_utf8_lossy(&name);
+        let name = name.trim_end_matches('\0');
+        if name == CPIO_TRAILER {
+            return Ok(());
+        }
+
+        let name = name.trim_start_matches("./").trim_start_matches('/');
+        let wanted = OS_RELEASE_PATHS.iter().find(|&&path| path == name);
+        match wanted {
+            Some(&path) if mode & S_IFMT == S_IFREG && size <= OS_RELEASE_MAX_LEN => {
+                let mut contents = Vec::with_capacity(size as usize);
+                (&mut archive).take(size).read_to_end(&mut contents)?;
+                if contents.len() as u64 != size {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+
+                trace!("found {} in the initramfs", path);
+                found.insert(path, contents);
+            }
+            _ => skip(&mut archive, size)?,
+        }
+
+        skip(&mut archive, padding(size))?;
+    }
+}
+
+/// The padding after a header or file to align the next to four bytes.
+fn padding(len: u64) -> u64 {
+    (4 - len % 4) % 4
+}
+
+fn skip<R: Read>(reader: R, len: u64) -> io::Result<()> {
+    if io::copy(&mut reader.take(len), &mut io::sink())? < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs;
+    use std::io::Write;
+
+    const OS_RELEASE: &str = "NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\nID=ubuntu\n";
+
+    /// Builds a newc cpio archive of `(name, mode, contents)` entries.
+    pub fn cpio(entries: &[(&str, u32, &str)]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let trailer = (CPIO_TRAILER, 0, "");
+        for (ino, &(name, mode, contents)) in entries.iter().chain(Some(&trailer)).enumerate() {
+            let fields = [ino as u32, mode, 0, 0, 1, 0, contents.len() as u32, 0, 0, 0, 0];
+            archive.extend_from_slice(b"070701");
+            for field in &fields {
+                archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+            }
+
+            archive.extend_from_slice(format!("{:08X}{:08X}", name.len() + 1, 0).as_bytes());
+            archive.extend_from_slice(name.as_bytes());
+            archive.push(0);
+            archive.resize(archive.len() + padding(archive.len() as u64) as usize, 0);
+            archive.extend_from_slice(contents.as_bytes());
+            archive.resize(archive.len() + padding(archive.len() as u64) as usize, 0);
+        }
+
+        archive
+    }
+
+    pub fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn gzip_initramfs() {
+        let image = gzip(&cpio(&[
+            (".", 0o040755, ""),
+            ("./usr/bin/busybox", 0o100755, "\x7fELF"),
+            ("./usr/lib/os-release", 0o100644, OS_RELEASE),
+            ("./etc/os-release", 0o120777, "../usr/lib/os-release"),
+        ]));
+
+        let release = extract_os_release(Cursor::new(image)).unwrap();
+        assert_eq!(release.as_deref(), Some(OS_RELEASE.as_bytes()));
+    }
+
+    #[test]
+    fn early_microcode_then_gzip() {
+        let mut image = cpio(&[("kernel/x86/microcode/GenuineIntel.bin", 0o100644, "ucode")]);
+        image.resize(512, 0);
+        image.extend(gzip(&cpio(&[("etc/os-release", 0o100644, OS_RELEASE)])));
+
+        let release = extract_os_release(Cursor::new(image)).unwrap();
+        assert_eq!(release.as_deref(), Some(OS_RELEASE.as_bytes()));
+    }
+
+    #[test]
+    fn early_microcode_then_zstd() {
+        // As built by dracut on Fedora, in which `etc/os-release` links to the
+        // `usr/lib/initrd-release` that describes the initramfs.
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/initramfs-zstd.img");
+        let release = extract_os_release(Cursor::new(fs::read(path).unwrap())).unwrap().unwrap();
+        let release = String::from_utf8(release).unwrap();
+        assert!(release.starts_with("NAME=\"Fedora Linux\"\n"), "{}", release);
+        assert!(release.contains("VERSION_ID=39\n"), "{}", release);
+    }
+
+    #[test]
+    fn initramfs_without_os_release() {
+        let image = cpio(&[("init", 0o100755, "#!/bin/sh\n")]);
+        assert_eq!(extract_os_release(Cursor::new(image)).unwrap(), None);
+        assert_eq!(extract_os_release(Cursor::new(b"BZh91AY&SY".to_vec())).unwrap(), None);
+        assert!(extract_os_release(Cursor::new(&cpio(&[])[..64])).is_err());
+    }
+
+    #[test]
+    fn cpio_name_too_long() {
+        let mut image = cpio(&[("etc/os-release", 0o100644, OS_RELEASE)]);
+        image[94..102].copy_from_slice(b"FFFFFFFF");
+        let why = extract_os_release(Cursor::new(image)).unwrap_err();
+        assert_eq!(why.kind(), io::ErrorKind::InvalidData);
+    }
+}
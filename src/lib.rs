@@ -14,211 +14,6005 @@
 //!     }
 //! }
 //! ```
+//!
+//! Detection also works on a plain directory, such as the contents of a disk image that
+//! were extracted with `7z` or `guestfish`, without mounting anything:
+//!
+//! ```rust,no_run
+//! extern crate os_detect;
+//!
+//! use os_detect::{detect_os_from_path, detect_os_from_path_all};
+//! use std::path::Path;
+//!
+//! pub fn main() {
+//!     let extracted = Path::new("image-contents");
+//!     println!("{:?}", detect_os_from_path(extracted));
+//!     for os in detect_os_from_path_all(extracted) {
+//!         println!("{}", os);
+//!     }
+//! }
+//! ```
+//!
+//! Each step of detection is logged under the `os_detect` target, so enabling trace logs
+//! for it, such as with `RUST_LOG=os_detect=trace`, shows why a device was or wasn't
+//! detected.
 
+#[cfg(feature = "initramfs")]
+extern crate flate2;
 #[macro_use]
 extern crate log;
 extern crate os_release;
 extern crate partition_identity;
+#[cfg(feature = "initramfs")]
+extern crate ruzstd;
+#[cfg(feature = "serde")]
+extern crate serde;
 extern crate sys_mount;
 extern crate tempdir;
 
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::path::Path;
-use tempdir::TempDir;
+#[cfg(feature = "async")]
+mod detect_future;
+mod gpt;
+mod grub;
+#[cfg(feature = "initramfs")]
+mod initramfs;
+mod partition_table;
+mod pe;
+mod registry;
+#[cfg(feature = "serde")]
+mod serialization;
+mod superblock;
+
 use os_release::OsRelease;
+use partition_identity::{PartitionID, PartitionIdentifiers, PartitionSource};
+use registry::Hive;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::iter::FromIterator;
 use std::path::PathBuf;
-use partition_identity::PartitionID;
+use std::path::{Component, Path};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
 use sys_mount::*;
+use tempdir::TempDir;
+
+#[cfg(feature = "async")]
+pub use detect_future::DetectFuture;
+pub use gpt::{classify_by_gpt_type, ExpectedOs};
+pub use grub::{parse_grub_config, read_grub_menu, GrubEntry, GrubMenu};
+pub use partition_table::{PartitionEntry, PartitionType};
 
 /// Describes the OS found on a partition.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[allow(clippy::large_enum_variant)]
 pub enum OS {
-    Windows(String),
-    Linux {
-        info: OsRelease,
-        partitions: Vec<PartitionID>,
-        targets: Vec<PathBuf>,
+    /// A Windows install, or a system reserved partition holding the Windows Boot Manager.
+    Windows {
+        /// The product name from the registry, such as `Windows 10 Pro`. Windows 11 still
+        /// records itself as `Windows 10`, so that's corrected by its build number.
+        ///
+        /// This is simply `Windows` if the registry couldn't be read, or `Windows Boot
+        /// Manager` for a system reserved partition.
+        product_name: String,
+        /// The feature update, such as `22H2`.
+        version: Option<String>,
+        /// The build number, such as `19045`.
+        build: Option<u32>,
+        /// The edition ID, such as `Professional`.
+        edition: Option<String>,
+        /// The architecture that Windows was installed for.
+        arch: Option<Arch>,
+        /// Names of the WSL distributions installed for the users of this install.
+        wsl: Vec<String>,
+        /// Whether Windows is hibernated, or was shut down with fast startup, as shown by
+        /// a non-empty `hiberfil.sys`.
+        ///
+        /// The NTFS volume is then in an unclean state, and callers should treat `true` as
+        /// "do not mount read-write", as changes may be lost or corrupt the volume on resume.
+        hibernated: bool,
+        /// Whether a `pagefile.sys` is present. It takes up space that the volume can give
+        /// back when resized, once virtual memory is disabled.
+        pagefile: bool,
+        /// Whether this is only a system reserved partition, with the `bootmgr` and BCD of
+        /// a Windows install on another partition.
+        boot_manager: bool,
+    },
+    /// A Linux install, along with the partitions found in its `/etc/fstab`.
+    Linux(LinuxInstall),
+    /// A macOS install, as described by its `SystemVersion.plist`.
+    MacOs {
+        /// The product name, such as `Mac OS X`.
+        product: String,
+        /// The user-visible version, such as `10.6.2`, which is empty for a Data volume.
+        version: String,
+        /// The build, such as `10C540`, which identifies the exact release.
+        build: Option<String>,
+    },
+    /// A ChromeOS, ChromiumOS, or ChromeOS Flex install, with its release description.
+    ChromeOs(String),
+    /// An Android system image, such as an Android-x86 install, with its release version.
+    Android(String),
+    /// A FreeBSD, OpenBSD, or NetBSD install.
+    Bsd {
+        /// Which BSD this is, such as `FreeBSD`.
+        flavor: String,
+        /// The release, such as `13.2-RELEASE`, if it could be determined.
+        version: Option<String>,
+    },
+    /// A partition with some of the files of an OS, but not enough to tell which.
+    Unknown {
+        /// What was seen, such as `etc/` for an `etc` directory without an os-release.
+        hints: Vec<String>,
     },
-    MacOs(String)
 }
 
-/// Mounts the partition to a temporary directory and checks for the existence of an
-/// installed operating system.
+/// The details of a Linux install, along with the partitions found in its `/etc/fstab`.
 ///
-/// If the installed operating system is Linux, it will also report back the location
-/// of the home partition.
-pub fn detect_os_from_device<'a, F: Into<FilesystemType<'a>>>(device: &Path, fs: F) -> Option<OS> {
-    // Create a temporary directoy where we will mount the FS.
-    TempDir::new("distinst").ok().and_then(|tempdir| {
-        // Mount the FS to the temporary directory
-        let base = tempdir.path();
-        Mount::new(device, base, fs, MountFlags::empty(), None)
-            .map(|m| m.into_unmount_drop(UnmountFlags::DETACH))
-            .ok()
-            .and_then(|_mount| detect_os_from_path(base))
-    })
+/// Since 0.3.0, this carries details beyond the fstab listing, and more may be added as
+/// detection improves. Destructure it with `..` to stay compatible.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LinuxInstall {
+    #[cfg_attr(feature = "serde", serde(with = "serialization::OsReleaseDef"))]
+    pub info: OsRelease,
+    /// Where `info` was read from.
+    pub source: OsReleaseSource,
+    /// The `VARIANT` of the os-release, such as `Workstation Edition`.
+    pub variant: Option<String>,
+    /// The `VARIANT_ID` of the os-release, such as `workstation`.
+    pub variant_id: Option<String>,
+    /// The IDs of the distributions that this one derives from, from the `ID_LIKE` of
+    /// the os-release, such as `["ubuntu", "debian"]`. See `OS::is_like`.
+    pub id_like: Vec<String>,
+    /// The `ANSI_COLOR` of the os-release, a terminal color for the distribution's name,
+    /// such as `0;31`.
+    pub ansi_color: Option<String>,
+    /// The `LOGO` of the os-release, the icon name of the distribution's logo, such as
+    /// `fedora-logo-icon`.
+    pub logo: Option<String>,
+    /// Every key of the os-release with its unquoted value, including those which
+    /// `info` doesn't cover, such as `LOGO`.
+    pub raw: BTreeMap<String, String>,
+    /// Sources of every entry in the fstab, in the same order as `targets`.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::vec_partition_id"))]
+    pub partitions: Vec<PartitionID>,
+    /// Mount targets of every entry in the fstab, in the same order as `partitions`.
+    pub targets: Vec<PathBuf>,
+    /// The identifiers of each partition in the fstab, and of the root, by where
+    /// they're mounted, such as `/boot/efi`.
+    ///
+    /// Only the identifier that the fstab lists a partition by is known when detecting
+    /// from a path. Detecting from a device looks up the rest among this system's
+    /// devices, for those which are attached.
+    pub part_refs: BTreeMap<PathBuf, PartRef>,
+    /// Where `/home` is mounted from, if separate from the root.
+    pub home: Option<MountSource>,
+    /// The EFI system partition mounted at `/boot/efi`.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::option_partition_id"))]
+    pub efi: Option<PartitionID>,
+    /// The separate `/boot` partition, which holds the kernels and bootloader
+    /// configuration when the root can't be read by the bootloader.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::option_partition_id"))]
+    pub boot: Option<PartitionID>,
+    /// The separate `/usr` partition, as used by some older or specialized layouts.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::option_partition_id"))]
+    pub usr: Option<PartitionID>,
+    /// Whether `/usr` is on a separate partition. Details detected from files within
+    /// `/usr`, such as the init system, may then be missing, as only the root is probed.
+    pub usr_separate: bool,
+    /// Where `/recovery` is mounted from.
+    pub recovery: Option<MountSource>,
+    /// Whether the recovery partition was confirmed to hold a recovery image.
+    ///
+    /// This can only be checked when the partition is mounted, and found through the
+    /// resolver given to `detect_linux_with_resolver`. It's `false` otherwise.
+    pub recovery_valid: bool,
+    /// The details of the recovery image, when `recovery_valid` is set.
+    pub recovery_image: Option<RecoveryImage>,
+    /// Where `/var` is mounted from, if separate from the root.
+    pub var: Option<MountSource>,
+    /// The install's machine ID, from `/etc/machine-id` or `/var/lib/dbus/machine-id`.
+    pub machine_id: Option<String>,
+    /// The hostname, from `/etc/hostname` or the `127.0.1.1` entry of `/etc/hosts`.
+    pub hostname: Option<String>,
+    /// The network configuration files which exist, relative to the root, such as
+    /// `etc/netplan/01-netcfg.yaml`, for migrating static addresses to a new install.
+    /// They're only listed, not parsed.
+    pub network_config: Vec<PathBuf>,
+    /// The timezone, such as `Europe/London`, from the `/etc/localtime` link or
+    /// `/etc/timezone`.
+    pub timezone: Option<String>,
+    /// The default locale, such as `en_GB.UTF-8`, from the `LANG` of `/etc/locale.conf`,
+    /// `/etc/default/locale`, or `/etc/environment`.
+    pub locale: Option<String>,
+    /// Roughly when the install was last used, as the latest modification time of the
+    /// files that are written to while it runs, such as `/var/log/wtmp` and the shell
+    /// history of each user. This tells the most recently used of several installs apart.
+    pub last_seen: LastSeen,
+    /// Versions of the kernels installed in `/boot` and `/lib/modules`, oldest first.
+    pub kernels: Vec<String>,
+    /// The partition that the install was detected on.
+    ///
+    /// This is only known when detecting from a device, rather than from a path.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::option_partition_id"))]
+    pub root: Option<PartitionID>,
+    /// Whether the root filesystem is a LUKS volume listed in `/etc/crypttab`.
+    pub root_encrypted: bool,
+    /// The type of the root filesystem by the install's own fstab, such as `btrfs`,
+    /// unless it's left as `auto`. The source of the entry is in `part_refs`.
+    pub root_fs: Option<String>,
+    /// Swap partitions listed in the fstab.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::vec_partition_id"))]
+    pub swap: Vec<PartitionID>,
+    /// The path of the swap file listed in the fstab, such as `/swapfile`, which takes
+    /// up space within the root or another filesystem rather than a partition.
+    pub swapfile: Option<String>,
+    /// The bootloader installed to the EFI partition, if it's mounted at `/boot/efi`.
+    pub bootloader: Option<Bootloader>,
+    /// The title of the GRUB menu entry that boots by default, or the `default` of the
+    /// `grub.cfg` as written if it names no entry there, as with the BLS entries of
+    /// Fedora. See `read_grub_menu` for the whole menu.
+    pub grub_default: Option<String>,
+    /// Whether the install boots through UEFI, BIOS, or both.
+    pub boot_mode: BootMode,
+    /// The architecture of the userland, from the ELF header of its shell or init.
+    pub arch: Option<Arch>,
+    /// The init system that the install boots with.
+    pub init_system: Option<InitSystem>,
+    /// The mandatory access control system that the install enables, if any.
+    pub security_module: Option<SecurityModule>,
+    /// The package manager whose database is present, which tells the distribution
+    /// that an install is based on, whatever its os-release claims.
+    pub package_manager: Option<PackageManager>,
+    /// The desktop environment, such as `GNOME`, if one could be told apart from any
+    /// others that are installed.
+    pub desktop: Option<String>,
+    /// Whether this looks like an install which can be booted, rather than a container
+    /// image: it has an init system, and a kernel in `/boot`, or a separate `/boot`
+    /// partition in its fstab.
+    pub is_bootable: bool,
+    /// Whether this is an OSTree system, such as Fedora Silverblue, in which case the
+    /// details are those of its current deployment.
+    pub ostree: bool,
 }
 
-/// Detects the existence of an OS at a defined path.
+impl fmt::Display for OS {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OS::Windows { ref product_name, ref version, build, .. } => {
+                f.write_str(product_name)?;
+                if let Some(ref version) = *version {
+                    write!(f, " {}", version)?;
+                }
+
+                match build {
+                    Some(build) => write!(f, " ({})", build),
+                    None => Ok(()),
+                }
+            }
+            OS::MacOs { ref product, ref version, ref build } => {
+                f.write_str(product)?;
+                if !version.is_empty() {
+                    write!(f, " ({})", version)?;
+                }
+
+                match *build {
+                    Some(ref build) => write!(f, " [{}]", build),
+                    None => Ok(()),
+                }
+            }
+            OS::ChromeOs(ref name) | OS::Android(ref name) => f.write_str(name),
+            OS::Bsd { ref flavor, version: Some(ref version) } => {
+                write!(f, "{} {}", flavor, version)
+            }
+            OS::Bsd { ref flavor, version: None } => f.write_str(flavor),
+            OS::Unknown { ref hints } => write!(f, "Unknown OS ({})", hints.join(", ")),
+            OS::Linux(LinuxInstall { ref info, .. }) if info.pretty_name.is_empty() => {
+                f.write_str(&info.name)
+            }
+            OS::Linux(LinuxInstall { ref info, .. }) => f.write_str(&info.pretty_name),
+        }
+    }
+}
+
+impl OS {
+    /// The name of the OS, without its version, such as `Ubuntu` or `Windows 10 Pro`.
+    ///
+    /// Linux installs are named by the `NAME` of their os-release, falling back to the
+    /// `PRETTY_NAME`, and then to `Linux`. ChromeOS is named by its full description.
+    pub fn name(&self) -> &str {
+        match *self {
+            OS::Windows { ref product_name, .. } => product_name,
+            OS::Linux(LinuxInstall { ref info, .. }) => linux_name(info),
+            OS::MacOs { ref product, .. } => product,
+            OS::ChromeOs(ref name) => name,
+            OS::Android(_) => "Android",
+            OS::Bsd { ref flavor, .. } => flavor,
+            OS::Unknown { .. } => "Unknown",
+        }
+    }
+
+    /// The version of the OS, such as the `VERSION_ID` of a Linux install, the feature
+    /// update or else the build of Windows, or the release of Android.
+    ///
+    /// ChromeOS descriptions don't have a separable version, so this is `None` for them.
+    pub fn version(&self) -> Option<String> {
+        match *self {
+            OS::Windows { ref version, build, .. } => {
+                version.clone().or_else(|| build.map(|build| build.to_string()))
+            }
+            OS::Linux(LinuxInstall { ref info, .. }) => linux_version(info),
+            OS::MacOs { ref version, .. } => {
+                Some(version.clone()).filter(|version| !version.is_empty())
+            }
+            OS::Android(ref name) => {
+                let release = name.strip_prefix("Android ")?;
+                release.split(' ').next().map(String::from)
+            }
+            OS::ChromeOs(_) => None,
+            OS::Bsd { ref version, .. } => version.clone(),
+            OS::Unknown { .. } => None,
+        }
+    }
+
+    /// Whether this is a Linux install of the distribution with the given os-release `ID`,
+    /// or of one derived from it, according to its `ID_LIKE`.
+    ///
+    /// For example, Pop!_OS, with `ID=pop` and `ID_LIKE="ubuntu debian"`, is like `pop`,
+    /// `ubuntu`, and `debian`.
+    pub fn is_like(&self, id: &str) -> bool {
+        match *self {
+            OS::Linux(LinuxInstall { ref info, ref id_like, .. }) => {
+                info.id == id || id_like.iter().any(|like| like == id)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The CPU architecture that an OS was installed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    PowerPc64,
+    Riscv64,
+    S390x,
+}
+
+impl Arch {
+    /// Whether this is a 64-bit architecture.
+    pub fn is_64_bit(self) -> bool {
+        !matches!(self, Arch::X86 | Arch::Arm)
+    }
+}
+
+/// Describes where a mount point of a Linux install is mounted from.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MountSource {
+    /// A partition of its own.
+    Partition {
+        #[cfg_attr(feature = "serde", serde(with = "serialization::PartitionIDDef"))]
+        id: PartitionID,
+        /// Whether the partition is a LUKS volume, mounted through a `/dev/mapper` name
+        /// which `/etc/crypttab` maps back to `id`.
+        encrypted: bool,
+    },
+    /// A btrfs subvolume, such as `@home`, on the same device as the root filesystem.
+    ///
+    /// Subvolumes on devices other than the root's are reported as a `Partition`.
+    Subvolume(String),
+    /// A LVM logical volume, named as `vg/lv`, from a `/dev/mapper/vg-lv` or `/dev/vg/lv`
+    /// source. The physical volumes backing it aren't resolved.
+    LvmVolume(String),
+}
+
+/// Where the os-release of a Linux install was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OsReleaseSource {
+    /// `/etc/os-release`, or wherever it links to.
+    Etc,
+    /// `/usr/lib/os-release`, in the absence of the former.
+    UsrLib,
+    /// The os-release embedded in an initramfs image in `/boot`, when the install has
+    /// neither. This is only checked with the `initramfs` feature.
+    Initramfs,
+}
+
+/// A Pop!_OS recovery image, as installed to a recovery partition by distinst.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecoveryImage {
+    /// The name of the OS that the image installs, such as `Pop!_OS`.
+    pub name: Option<String>,
+    /// The version of the OS that the image installs, such as `22.04`.
+    pub version: Option<String>,
+    /// The `KEY=value` settings of its `recovery.conf`, such as `HOSTNAME` and `ROOT_UUID`.
+    pub config: BTreeMap<String, String>,
+}
+
+/// Roughly when an install was last used, if any of the files written to as it runs exist.
 ///
-/// This function is called by `detect_os_from_device`, after having temporarily mounted it.
-pub fn detect_os_from_path(base: &Path) -> Option<OS> {
-    detect_linux(base)
-        .or_else(|| detect_windows(base))
-        .or_else(|| detect_macos(base))
+/// Every `LastSeen` is equal to every other, so that detections of the same install are
+/// equal even once it has been used again in between.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LastSeen(pub Option<SystemTime>);
+
+impl PartialEq for LastSeen {
+    fn eq(&self, _other: &LastSeen) -> bool {
+        true
+    }
 }
 
-/// Detect if Linux is installed at the given path.
-pub fn detect_linux(base: &Path) -> Option<OS> {
-    let path = base.join("etc/os-release");
-    if path.exists() {
-        if let Ok(info) = OsRelease::new_from(path) {
-            let (partitions, targets) = find_linux_parts(base);
-            return Some(OS::Linux { info, partitions, targets });
+/// The identifiers of a partition, by which an fstab or bootloader configuration may
+/// refer to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PartRef {
+    /// The UUID of the filesystem, as in `UUID=`.
+    pub uuid: Option<String>,
+    /// The UUID of the partition in its partition table, as in `PARTUUID=`.
+    pub partuuid: Option<String>,
+    /// The label of the filesystem, as in `LABEL=`.
+    pub label: Option<String>,
+}
+
+impl PartRef {
+    /// The identifiers known from a single `PartitionID`, such as that of an fstab entry.
+    pub fn from_id(id: &PartitionID) -> PartRef {
+        let mut part = PartRef::default();
+        match id.variant {
+            PartitionSource::UUID => part.uuid = Some(id.id.clone()),
+            PartitionSource::PartUUID => part.partuuid = Some(id.id.clone()),
+            PartitionSource::Label => part.label = Some(id.id.clone()),
+            _ => (),
         }
+
+        part
     }
 
-    None
+    /// Fills in whichever identifiers are missing from those found for the device.
+    fn complete(&mut self, found: PartitionIdentifiers) {
+        self.uuid = self.uuid.take().or(found.uuid);
+        self.partuuid = self.partuuid.take().or(found.part_uuid);
+        self.label = self.label.take().or(found.label);
+    }
 }
 
-/// Detect if Mac OS is installed at the given path.
-pub fn detect_macos(base: &Path) -> Option<OS> {
-    open(base.join("etc/os-release"))
-        .ok()
-        .and_then(|file| {
-            parse_plist(BufReader::new(file))
-                .or_else(|| Some("Mac OS (Unknown)".into()))
-                .map(OS::MacOs)
-        })
+/// The init system of a Linux install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InitSystem {
+    Systemd,
+    OpenRc,
+    SysVinit,
 }
 
-/// Detect if Windows is installed at the given path.
-pub fn detect_windows(base: &Path) -> Option<OS> {
-    // TODO: More advanced version-specific detection is possible.
-    base.join("Windows/System32/ntoskrnl.exe")
-        .exists()
-        .map(|| OS::Windows("Windows".into()))
-}
-
-fn find_linux_parts(base: &Path) -> (Vec<PartitionID>, Vec<PathBuf>) {
-    let mut partitions = Vec::new();
-    let mut targets = Vec::new();
-
-    if let Ok(fstab) = open(base.join("etc/fstab")) {
-        for entry in BufReader::new(fstab).lines() {
-            if let Ok(entry) = entry {
-                let entry = entry.trim();
-                if entry.starts_with('#') || entry.is_empty() {
-                    continue;
-                }
+/// A mandatory access control system of a Linux install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SecurityModule {
+    /// SELinux, which is either enforcing its policy, or permissive and only logging
+    /// denials.
+    SeLinux {
+        enforcing: bool,
+    },
+    AppArmor,
+}
 
-                let mut fields = entry.split_whitespace();
-                let source = fields.next();
-                let target = fields.next();
+/// The package manager of a Linux install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PackageManager {
+    /// Debian and its derivatives, with apt.
+    Dpkg,
+    /// Fedora, openSUSE, and other RPM-based distributions.
+    Rpm,
+    /// Arch Linux and its derivatives.
+    Pacman,
+    /// Alpine Linux.
+    Apk,
+}
 
-                if let Some(target) = target {
-                    if let Some(Ok(path)) = source.map(|s| s.parse::<PartitionID>()) {
-                        partitions.push(path);
-                        targets.push(PathBuf::from(String::from(target)));
-                    }
-                }
+/// A bootloader found on an EFI system partition.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Bootloader {
+    Grub,
+    SystemdBoot,
+    WindowsBootManager,
+}
+
+/// An OS detected by `detect_detailed`, along with what it was detected from.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Detection {
+    pub os: OS,
+    /// How complete the detected install is.
+    pub confidence: Confidence,
+    /// The files, and keys within them, which the detection is based on, such as
+    /// `etc/os-release` and `ID=pop`. Paths are relative to the detected path.
+    pub evidence: Vec<String>,
+}
+
+/// The kinds of OS which can be detected, each by its own detector. See `detect_specific`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OsKind {
+    Bsd,
+    Linux,
+    ChromeOs,
+    Android,
+    Windows,
+    MacOs,
+}
+
+/// The name of a Linux install by its os-release, for `OS::name`.
+fn linux_name(info: &OsRelease) -> &str {
+    if !info.name.is_empty() {
+        &info.name
+    } else if !info.pretty_name.is_empty() {
+        &info.pretty_name
+    } else {
+        "Linux"
+    }
+}
+
+/// The version of a Linux install by its os-release, for `OS::version`.
+fn linux_version(info: &OsRelease) -> Option<String> {
+    Some(info.version_id.clone()).filter(|version| !version.is_empty())
+}
+
+/// The name and version of a detected OS, without the rest of its details, as returned by
+/// `detect_os_summary`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OsSummary {
+    pub kind: OsKind,
+    /// The name of the OS, as from `OS::name`.
+    pub name: String,
+    /// The version of the OS, as from `OS::version`.
+    pub version: Option<String>,
+}
+
+impl OsSummary {
+    /// Summarizes a detected OS, unless it's an `OS::Unknown`, which has no kind.
+    pub fn of(os: &OS) -> Option<OsSummary> {
+        let kind = match *os {
+            OS::Bsd { .. } => OsKind::Bsd,
+            OS::Linux(LinuxInstall { .. }) => OsKind::Linux,
+            OS::ChromeOs(_) => OsKind::ChromeOs,
+            OS::Android(_) => OsKind::Android,
+            OS::Windows { .. } => OsKind::Windows,
+            OS::MacOs { .. } => OsKind::MacOs,
+            OS::Unknown { .. } => return None,
+        };
+
+        Some(OsSummary { kind, name: os.name().to_owned(), version: os.version() })
+    }
+}
+
+/// How strongly the signals of a detection point to an installed OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Confidence {
+    /// A complete install, which has everything needed to boot.
+    Full,
+    /// Only the boot files of an OS installed elsewhere, such as the Windows Boot Manager
+    /// on a system reserved partition.
+    BootOnly,
+    /// A userland without a kernel or init system to boot it, such as a container rootfs.
+    Container,
+    /// Part of an OS, such as a macOS Data volume, or the hints of an `OS::Unknown`.
+    Partial,
+}
+
+/// The firmware interfaces that a Linux install is set up to boot from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BootMode {
+    /// An EFI system partition is mounted at `/boot/efi`.
+    Uefi,
+    /// GRUB's BIOS modules are installed, for booting from the MBR.
+    Bios,
+    /// Both, such as an install to a removable drive that boots on either firmware.
+    Hybrid,
+    /// Neither was found, as in a container image.
+    Unknown,
+}
+
+/// Errors that may occur while probing a device for an installed OS.
+#[derive(Debug)]
+pub enum DetectError {
+    /// The temporary directory to mount the device to could not be created.
+    TempDir(io::Error),
+    /// The device could not be mounted with the given filesystem.
+    Mount { device: PathBuf, fs: String, why: io::Error },
+    /// An I/O error occurred while probing the mounted device.
+    Io(io::Error),
+    /// The device could not be mounted and probed within the allotted time.
+    Timeout { device: PathBuf, timeout: Duration },
+    /// An `os-release` file exists, but could not be read or is not an os-release file.
+    OsRelease { path: PathBuf, why: io::Error },
+    /// The superblock of the device shows a different filesystem than the one it was to be
+    /// mounted as. Only returned by a `strict` detector.
+    FilesystemMismatch { device: PathBuf, declared: String, found: String },
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DetectError::TempDir(ref why) => {
+                write!(f, "unable to create a temporary directory to mount to: {}", why)
+            }
+            DetectError::Mount { ref device, ref fs, ref why } => {
+                write!(f, "unable to mount {:?} as {}: {}", device, fs, why)
+            }
+            DetectError::Io(ref why) => write!(f, "I/O error while probing for an OS: {}", why),
+            DetectError::Timeout { ref device, timeout } => {
+                write!(f, "timed out after {:?} while probing {:?}", timeout, device)
+            }
+            DetectError::OsRelease { ref path, ref why } => {
+                write!(f, "unable to read the os-release at {:?}: {}", path, why)
+            }
+            DetectError::FilesystemMismatch { ref device, ref declared, ref found } => {
+                write!(f, "{:?} was to be mounted as {}, but holds {}", device, declared, found)
             }
         }
     }
+}
 
-    (partitions, targets)
+impl Error for DetectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            DetectError::TempDir(ref why)
+            | DetectError::Mount { ref why, .. }
+            | DetectError::Io(ref why)
+            | DetectError::OsRelease { ref why, .. } => Some(why),
+            DetectError::Timeout { .. } | DetectError::FilesystemMismatch { .. } => None,
+        }
+    }
 }
 
-fn parse_plist<R: BufRead>(file: R) -> Option<String> {
-    // The plist is an XML file, but we don't need complex XML parsing for this.
-    let mut product_name: Option<String> = None;
-    let mut version: Option<String> = None;
-    let mut flags = 0;
+/// Mounts the partition to a temporary directory and checks for the existence of an
+/// installed operating system.
+///
+/// If the installed operating system is Linux, it will also report back the location
+/// of the home partition.
+///
+/// Filesystems with more than one kernel driver are mounted with whichever the kernel has:
+/// `ntfs` is tried as `ntfs3` and then `ntfs`, and `vfat` as `vfat` and then `msdos`.
+///
+/// Errors are discarded. Use `try_detect_os_from_device` to find out why a device
+/// could not be probed.
+pub fn detect_os_from_device<'a, F: Into<FilesystemType<'a>>>(device: &Path, fs: F) -> Option<OS> {
+    try_detect_os_from_device(device, fs).ok().flatten()
+}
 
-    for entry in file.lines().flat_map(|line| line) {
-        let entry = entry.trim();
-        match flags {
-            0 => match entry {
-                "<key>ProductUserVisibleVersion</key>" => flags = 1,
-                "<key>ProductName</key>" => flags = 2,
-                _ => (),
-            },
-            1 => {
-                if entry.len() < 10 {
-                    return None;
-                }
-                version = Some(entry[8..entry.len() - 9].into());
-                flags = 0;
+/// Mounts the partition to a temporary directory and checks for the existence of an
+/// installed operating system, reporting why the device couldn't be probed on failure.
+///
+/// `Ok(None)` means that the device was mounted, but no OS was found on it.
+pub fn try_detect_os_from_device<'a, F: Into<FilesystemType<'a>>>(
+    device: &Path,
+    fs: F,
+) -> Result<Option<OS>, DetectError> {
+    Detector::default().detect(device, fs)
+}
+
+/// Like `try_detect_os_from_device`, but returns a future, for async callers which can't
+/// block on mounting and probing. Enabled by the `async` feature.
+///
+/// The probe runs on a thread of its own rather than on the runtime, so this works with
+/// any executor. See `DetectFuture`.
+#[cfg(feature = "async")]
+pub fn detect_os_from_device_async<'a, F: Into<FilesystemType<'a>>>(
+    device: &Path,
+    fs: F,
+) -> DetectFuture {
+    Detector::default().detect_async(device, fs)
+}
+
+/// Like `try_detect_os_from_device`, but passes the given `data` as filesystem-specific
+/// mount options, for filesystems that need them to be mounted for probing.
+///
+/// For example, NTFS-3G may need `windows_names`, and `noload` is recommended for ext4
+/// volumes, as it ensures that the journal is not replayed during a read-only probe.
+pub fn detect_os_from_device_with_options<'a, F: Into<FilesystemType<'a>>>(
+    device: &Path,
+    fs: F,
+    data: Option<&str>,
+) -> Result<Option<OS>, DetectError> {
+    Detector::default().mount_data(data.map(String::from)).detect(device, fs)
+}
+
+/// Checks each of the given devices for an installed OS, several at a time.
+///
+/// Each device is mounted read-only, to its own temporary directory, exactly as with
+/// `detect_os_from_device`. The results are in the same order as `devices`, with errors
+/// logged and discarded.
+pub fn detect_os_from_devices(devices: &[(PathBuf, FilesystemType)]) -> Vec<(PathBuf, Option<OS>)> {
+    Detector::default().detect_devices(devices)
+}
+
+/// Checks a partition within a raw disk image file, such as a `.img` or `.raw`, for an
+/// installed OS.
+///
+/// The partition starts `offset` bytes into the image, which is zero for an image of a
+/// single filesystem, or the partition's start sector multiplied by the sector size for an
+/// image of a whole disk. The image is attached to a loop device with `losetup` for each
+/// mount attempt, so this needs root, util-linux, and a kernel with loop device support.
+pub fn detect_os_from_image<'a, F: Into<FilesystemType<'a>>>(
+    image: &Path,
+    offset: u64,
+    fs: F,
+) -> Result<Option<OS>, DetectError> {
+    Detector::default().mounter(LoopMounter { offset, size: None }).detect(image, fs)
+}
+
+/// Checks each partition of a whole-disk image for an installed OS, after reading them
+/// from the image's GPT or MBR partition table.
+///
+/// Each partition is mounted as with `detect_os_from_image`, but with its loop device
+/// ending where the partition does, and its filesystem probed from its superblock.
+/// Partitions which can't hold an OS, such as swap and EFI system partitions, are listed
+/// without being probed. Nothing is found if the partition table can't be read.
+pub fn detect_os_from_disk_image(image: &Path) -> Vec<(PartitionEntry, Option<OS>)> {
+    let mut file = match open(image) {
+        Ok(file) => file,
+        Err(why) => {
+            warn!("{}", why);
+            return Vec::new();
+        }
+    };
+
+    let partitions = match partition_table::read(&mut file) {
+        Ok(partitions) => partitions,
+        Err(why) => {
+            warn!("unable to read the partition table of {:?}: {}", image, why);
+            return Vec::new();
+        }
+    };
+
+    partitions
+        .into_iter()
+        .map(|partition| {
+            if !partition.may_hold_os() {
+                trace!("skipping partition {} of {:?}", partition.number, image);
+                return (partition, None);
             }
-            2 => {
-                if entry.len() < 10 {
-                    return None;
-                }
-                product_name = Some(entry[8..entry.len() - 9].into());
-                flags = 0;
+
+            let probed = file
+                .seek(SeekFrom::Start(partition.offset))
+                .and_then(|_| superblock::probe(&mut file))
+                .unwrap_or(None);
+            if probed == Some("swap") {
+                return (partition, None);
             }
-            _ => unreachable!(),
+
+            // The loop device ends with the partition, so that no mount can reach the next.
+            let candidates = auto_candidates(probed);
+            let mounter = LoopMounter { offset: partition.offset, size: Some(partition.size) };
+            let os = detect_with_candidates(&candidates, |fs| {
+                Detector::default().mounter(mounter).detect(image, fs)
+            });
+            (partition, os)
+        })
+        .collect()
+}
+
+/// Mounts devices for a `Detector` to probe.
+///
+/// `SysMounter`, which mounts devices with the `mount` system call, is used by default.
+/// Another implementation can stand in for it, such as to test detection of a device
+/// without the privileges or devices to mount it.
+pub trait Mounter: fmt::Debug + Send + Sync {
+    /// Mounts the `device` at the existing `target` directory.
+    ///
+    /// The returned mount is unmounted once the device has been probed, after which the
+    /// `target` directory is removed.
+    fn mount(
+        &self,
+        device: &Path,
+        target: &Path,
+        fs: &FilesystemType,
+        flags: MountFlags,
+        data: Option<&str>,
+    ) -> io::Result<Box<dyn Unmount>>;
+
+    /// Where the filesystem starts within the device, in bytes, for mounters which mount
+    /// part of it, such as a partition of a disk image. Its superblock is probed there.
+    fn offset(&self) -> u64 {
+        0
+    }
+}
+
+/// Mounts devices with the `mount` system call, which requires root.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SysMounter;
+
+impl Mounter for SysMounter {
+    fn mount(
+        &self,
+        device: &Path,
+        target: &Path,
+        fs: &FilesystemType,
+        flags: MountFlags,
+        data: Option<&str>,
+    ) -> io::Result<Box<dyn Unmount>> {
+        let mount = Mount::new(device, target, copy_fs(fs), flags, data)?;
+        debug!("mounted {:?} with {} at {:?}", device, mount.get_fstype(), target);
+        Ok(Box::new(mount))
+    }
+}
+
+/// Mounts a partition of an image file by attaching it to a loop device at its offset,
+/// and of its size, if known.
+#[derive(Debug, Clone, Copy)]
+struct LoopMounter {
+    offset: u64,
+    size: Option<u64>,
+}
+
+impl Mounter for LoopMounter {
+    fn mount(
+        &self,
+        image: &Path,
+        target: &Path,
+        fs: &FilesystemType,
+        flags: MountFlags,
+        data: Option<&str>,
+    ) -> io::Result<Box<dyn Unmount>> {
+        let read_only = flags.contains(MountFlags::RDONLY);
+        let output = losetup_attach(image, self.offset, self.size, read_only).output()?;
+        if !output.status.success() {
+            let why = String::from_utf8_lossy(&output.stderr);
+            return Err(io::Error::other(format!("losetup failed: {}", why.trim())));
         }
-        if product_name.is_some() && version.is_some() {
-            break;
+
+        let device = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+        debug!("attached {:?} at offset {} to {:?}", image, self.offset, device);
+        match SysMounter.mount(&device, target, fs, flags, data) {
+            Ok(mount) => Ok(Box::new(LoopMount { mount, device })),
+            Err(why) => {
+                let _ = losetup_detach(&device);
+                Err(why)
+            }
         }
     }
 
-    if let (Some(name), Some(version)) = (product_name, version) {
-        Some(format!("{} ({})", name, version))
-    } else {
-        None
+    fn offset(&self) -> u64 {
+        self.offset
     }
 }
 
-fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
-    File::open(&path).map_err(|why| io::Error::new(
-        io::ErrorKind::Other,
-        format!("unable to open file at {:?}: {}", path.as_ref(), why)
-    ))
+/// The `losetup` command which attaches the partition at `offset` of an image, and `size`
+/// bytes long if given, to the next free loop device, and prints the device's path.
+fn losetup_attach(image: &Path, offset: u64, size: Option<u64>, read_only: bool) -> Command {
+    let mut command = Command::new("losetup");
+    command.args(["--find", "--show", "--offset"]).arg(offset.to_string());
+    if let Some(size) = size {
+        command.arg("--sizelimit").arg(size.to_string());
+    }
+
+    if read_only {
+        command.arg("--read-only");
+    }
+
+    command.arg(image);
+    command
 }
 
-/// Adds a new map method for boolean types.
-pub(crate) trait BoolExt {
-    fn map<T, F: Fn() -> T>(&self, action: F) -> Option<T>;
+/// Detaches a loop device. If it's still in use, as after a lazy unmount, the kernel
+/// detaches it once it's released.
+fn losetup_detach(device: &Path) -> io::Result<()> {
+    let output = Command::new("losetup").arg("--detach").arg(device).output()?;
+    if !output.status.success() {
+        let why = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("unable to detach {:?}: {}", device, why.trim())));
+    }
+
+    Ok(())
 }
 
-impl BoolExt for bool {
-    fn map<T, F: Fn() -> T>(&self, action: F) -> Option<T> {
-        if *self {
-            Some(action())
-        } else {
-            None
+/// A mount of a loop device, which detaches the device once unmounted.
+struct LoopMount {
+    mount: Box<dyn Unmount>,
+    device: PathBuf,
+}
+
+impl Unmount for LoopMount {
+    /// Fails only if the unmount does. Once unmounted there's nothing to retry, so a
+    /// device which can't be detached is logged and left attached.
+    fn unmount(&self, flags: UnmountFlags) -> io::Result<()> {
+        self.mount.unmount(flags)?;
+        if let Err(why) = losetup_detach(&self.device) {
+            warn!("{:?} was unmounted, but not detached: {}", self.device, why);
         }
+
+        Ok(())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Cursor;
+/// Configures how devices are mounted while they are probed for an installed OS.
+///
+/// The `detect_os_from_device` family of functions use the default configuration.
+///
+/// ```rust,no_run
+/// extern crate os_detect;
+///
+/// use os_detect::Detector;
+/// use std::path::Path;
+///
+/// pub fn main() {
+///     let detector = Detector::new()
+///         .temp_prefix("my-installer")
+///         .mount_data(Some("noload".into()));
+///
+///     match detector.detect(Path::new("/dev/sda3"), "ext4") {
+///         Ok(Some(os)) => println!("found {}", os),
+///         Ok(None) => println!("no OS found"),
+///         Err(why) => eprintln!("unable to probe: {:?}", why),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Detector {
+    temp_prefix: String,
+    fallback_temp_dir: PathBuf,
+    read_only: bool,
+    mount_flags: MountFlags,
+    mount_data: Option<String>,
+    timeout: Option<Duration>,
+    strict: bool,
+    mounter: Arc<dyn Mounter>,
+}
 
-    const MAC_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
-<!DOCTYPE plist PUBLIC "Apple Stuff">
-<plist version="1.0">
-<dict>
-    <key>ProductBuildVersion</key>
-    <string>10C540</string>
-    <key>ProductName</key>
-    <string>Mac OS X</string>
-    <key>ProductUserVisibleVersion</key>
-    <string>10.6.2</string>
-    <key>ProductVersion</key>
-    <string>10.6.2</string>
-</dict>
-</plist>"#;
+impl Default for Detector {
+    fn default() -> Self {
+        Detector {
+            temp_prefix: "os-detect".into(),
+            fallback_temp_dir: PathBuf::from("/run"),
+            read_only: true,
+            mount_flags: MountFlags::empty(),
+            mount_data: None,
+            timeout: None,
+            strict: false,
+            mounter: Arc::new(SysMounter),
+        }
+    }
+}
 
-    #[test]
-    fn mac_plist_parsing() {
-        assert_eq!(
-            parse_plist(Cursor::new(MAC_PLIST)),
-            Some("Mac OS X (10.6.2)".into())
-        );
+impl Detector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the prefix of the temporary directories which devices are mounted to.
+    ///
+    /// Defaults to `os-detect`.
+    pub fn temp_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.temp_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the directory to create the temporary directories in when the system's
+    /// temporary directory, from `TMPDIR` or else `/tmp`, can't be written to, as on live
+    /// systems whose `/tmp` is read-only. Defaults to `/run`.
+    pub fn fallback_temp_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.fallback_temp_dir = dir.into();
+        self
+    }
+
+    /// Sets what mounts devices to be probed. Defaults to `SysMounter`.
+    pub fn mounter<M: Mounter + 'static>(mut self, mounter: M) -> Self {
+        self.mounter = Arc::new(mounter);
+        self
+    }
+
+    /// Sets whether devices should be mounted read-only. Defaults to `true`.
+    ///
+    /// Some drivers refuse to mount read-only, in which case a read-write mount is attempted
+    /// anyway, with a warning. When `false`, devices are mounted read-write straight away.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sets additional flags to mount devices with.
+    ///
+    /// These are combined with `MountFlags::RDONLY` on read-only mount attempts.
+    pub fn mount_flags(mut self, flags: MountFlags) -> Self {
+        self.mount_flags = flags;
+        self
+    }
+
+    /// Sets filesystem-specific options to mount devices with.
+    ///
+    /// For example, NTFS-3G may need `windows_names`, and `noload` is recommended for ext4
+    /// volumes, as it ensures that the journal is not replayed during a read-only probe.
+    pub fn mount_data(mut self, data: Option<String>) -> Self {
+        self.mount_data = data;
+        self
+    }
+
+    /// Sets how long to wait for a device to be mounted and probed before giving up, as
+    /// can be needed with flaky USB or network-backed media. Defaults to waiting forever.
+    ///
+    /// With a timeout, the probe runs on a worker thread. A mount which is still in progress
+    /// when the timeout fires can't be interrupted, so it's left to finish in the
+    /// background, after which the worker unmounts it and removes its temporary directory.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets whether a device is refused when its superblock shows a different filesystem
+    /// than the one it's to be mounted as, with `DetectError::FilesystemMismatch`. Defaults
+    /// to `false`, in which case the mismatch is only logged as a warning.
+    ///
+    /// Devices whose filesystem isn't recognized, and those mounted with
+    /// `FilesystemType::Auto`, are never refused.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Mounts the device to a temporary directory and checks for an installed OS.
+    ///
+    /// `Ok(None)` means that the device was mounted, but no OS was found on it. If the only
+    /// sign of an OS is an `/etc/os-release` which can't be parsed, `DetectError::OsRelease`
+    /// is returned instead.
+    pub fn detect<'a, F: Into<FilesystemType<'a>>>(
+        &self,
+        device: &Path,
+        fs: F,
+    ) -> Result<Option<OS>, DetectError> {
+        let fs = fs.into();
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return self.detect_now(device, &fs),
+        };
+
+        let detector = Detector { timeout: None, ..self.clone() };
+        let (worker_device, fs) = (device.to_path_buf(), OwnedFilesystemType::from(&fs));
+        let probe = move || fs.with(|fs| detector.detect_now(&worker_device, &fs));
+        run_with_timeout(timeout, probe).unwrap_or_else(|| {
+            warn!("timed out after {:?} while probing {:?}", timeout, device);
+            Err(DetectError::Timeout { device: device.to_path_buf(), timeout })
+        })
+    }
+
+    /// Like `detect`, but returns a future which resolves once a worker thread has probed
+    /// the device. See `detect_os_from_device_async`.
+    #[cfg(feature = "async")]
+    pub fn detect_async<'a, F>(&self, device: &Path, fs: F) -> DetectFuture
+    where
+        F: Into<FilesystemType<'a>>,
+    {
+        let detector = self.clone();
+        let (device, fs) = (device.to_path_buf(), OwnedFilesystemType::from(&fs.into()));
+        DetectFuture::spawn(move || fs.with(|fs| detector.detect(&device, fs)))
+    }
+
+    /// Checks each of the given devices for an installed OS, with up to four probed at a
+    /// time. See `detect_os_from_devices`.
+    pub fn detect_devices(
+        &self,
+        devices: &[(PathBuf, FilesystemType)],
+    ) -> Vec<(PathBuf, Option<OS>)> {
+        run_parallel(devices, PARALLEL_WORKERS, |(device, fs)| {
+            let os = match self.detect(device, copy_fs(fs)) {
+                Ok(os) => os,
+                Err(why) => {
+                    debug!("unable to probe {:?}: {:?}", device, why);
+                    None
+                }
+            };
+
+            (device.clone(), os)
+        })
+    }
+
+    /// Reports what `detect` would do to probe the device, without mounting anything, such
+    /// as to log or test how a detector is configured where devices can't be mounted.
+    pub fn plan<'a, F: Into<FilesystemType<'a>>>(&self, device: &Path, fs: F) -> DetectionPlan {
+        let fs = fs.into();
+        let drivers = filesystem_drivers(&fs);
+        let mounts = mount_attempts(self.read_only)
+            .iter()
+            .flat_map(|&flags| {
+                let flags = flags | self.mount_flags;
+                drivers.iter().map(move |fs| (filesystem_name(fs), flags))
+            })
+            .collect();
+
+        DetectionPlan {
+            device: device.to_path_buf(),
+            temp_dirs: self.temp_dirs(),
+            temp_prefix: self.temp_prefix.clone(),
+            mounts,
+            mount_data: self.mount_data.clone(),
+            timeout: self.timeout,
+            detectors: DETECTORS.iter().map(|&(_, name, _)| name).collect(),
+        }
+    }
+
+    /// The directories to create temporary mount directories in, in order of preference.
+    fn temp_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![std::env::temp_dir(), self.fallback_temp_dir.clone()];
+        dirs.dedup();
+        dirs
+    }
+
+    /// Compares the filesystem that a device is to be mounted as with the one its
+    /// superblock shows, as a driver may mount the wrong filesystem without complaint.
+    fn check_filesystem(&self, device: &Path, fs: &FilesystemType) -> Result<(), DetectError> {
+        let declared: Vec<&str> = match *fs {
+            FilesystemType::Manual(fs) => vec![fs],
+            FilesystemType::Set(set) => set.to_vec(),
+            FilesystemType::Auto(_) => return Ok(()),
+        };
+
+        let found = match probe_filesystem_at(device, self.mounter.offset()) {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+
+        if declared.iter().any(|&fs| same_filesystem(fs, &found)) {
+            return Ok(());
+        }
+
+        let declared = declared.join(", ");
+        if self.strict {
+            let device = device.to_path_buf();
+            return Err(DetectError::FilesystemMismatch { device, declared, found });
+        }
+
+        warn!("{:?} is to be mounted as {}, but its superblock shows {}", device, declared, found);
+        Ok(())
+    }
+
+    fn detect_now(&self, device: &Path, fs: &FilesystemType) -> Result<Option<OS>, DetectError> {
+        let fs_name = filesystem_name(fs);
+        self.check_filesystem(device, fs)?;
+
+        // Create a temporary directoy where we will mount the FS.
+        let tempdir = create_temp_dir(&self.temp_dirs(), &self.temp_prefix)?;
+        let base = tempdir.path().to_path_buf();
+        debug!("created {:?} to mount {:?} at", base, device);
+
+        // Mount the FS to the temporary directory
+        let data = self.mount_data.as_deref();
+        let mounter = &*self.mounter;
+        let mount = mount_probe(mounter, device, &base, fs, self.read_only, self.mount_flags, data)
+            .map_err(|why| DetectError::Mount { device: device.to_path_buf(), fs: fs_name, why })?;
+
+        // From here on, the temporary directory must only be removed by the guard, once the
+        // device is unmounted, even if detection panics.
+        let _mount = MountGuard::new(mount, tempdir);
+
+        let mut os = match detect_all(&base).into_iter().next() {
+            Some(os) => os,
+            // Tell a damaged Linux install apart from a device with no OS.
+            None => return try_detect_linux(&base).map(|os| os.or_else(|| detect_unknown(&base))),
+        };
+
+        set_root_device(&mut os, device);
+        check_root_fs(&os, device, fs);
+        Ok(Some(os))
+    }
+}
+
+/// Warns if a Linux install's fstab has a different type for its root filesystem than
+/// the one its device was mounted as.
+fn check_root_fs(os: &OS, device: &Path, fs: &FilesystemType) {
+    let root_fs = match *os {
+        OS::Linux(LinuxInstall { root_fs: Some(ref root_fs), .. }) => root_fs,
+        _ => return,
+    };
+
+    if let FilesystemType::Manual(fs) = *fs {
+        if !same_filesystem(fs, root_fs) {
+            warn!("{:?} was mounted as {}, but its fstab has {} for /", device, fs, root_fs);
+        }
+    }
+}
+
+/// Creates a temporary directory in the first of `dirs` that it can be created in. The
+/// error names each directory that was tried, and why it couldn't be used.
+fn create_temp_dir(dirs: &[PathBuf], prefix: &str) -> Result<TempDir, DetectError> {
+    let mut failures = Vec::new();
+    let mut kind = io::ErrorKind::NotFound;
+    for dir in dirs {
+        match TempDir::new_in(dir, prefix) {
+            Ok(tempdir) => return Ok(tempdir),
+            Err(why) => {
+                debug!("unable to create a temporary directory in {:?}: {}", dir, why);
+                if failures.is_empty() {
+                    kind = why.kind();
+                }
+
+                failures.push(format!("{:?}: {}", dir, why));
+            }
+        }
+    }
+
+    Err(DetectError::TempDir(io::Error::new(kind, failures.join(", "))))
+}
+
+/// What a `Detector` would do to probe a device, as reported by `Detector::plan`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectionPlan {
+    /// The device to be mounted.
+    pub device: PathBuf,
+    /// The directories that the temporary mount directory would be created in, in order of
+    /// preference.
+    pub temp_dirs: Vec<PathBuf>,
+    /// The prefix of the temporary mount directory's name, which is followed by a random
+    /// suffix.
+    pub temp_prefix: String,
+    /// The filesystem driver and flags of each mount to be attempted, in order, until one
+    /// succeeds.
+    pub mounts: Vec<(String, MountFlags)>,
+    /// The filesystem-specific options passed with each mount.
+    pub mount_data: Option<String>,
+    /// How long the probe may take before it's abandoned.
+    pub timeout: Option<Duration>,
+    /// The names of the detectors to be run against the mounted device, in order of
+    /// precedence.
+    pub detectors: Vec<&'static str>,
+}
+
+/// Unmounts a device from its temporary directory when dropped, and only then removes
+/// the directory.
+///
+/// The order matters: removing the directory recursively while the device is still
+/// mounted on it would delete the contents of the device. So if the device can't be
+/// unmounted, even lazily, the directory is left behind instead.
+struct MountGuard<'a> {
+    mount: Box<dyn Unmount + 'a>,
+    tempdir: Option<TempDir>,
+}
+
+impl<'a> MountGuard<'a> {
+    fn new(mount: Box<dyn Unmount + 'a>, tempdir: TempDir) -> Self {
+        MountGuard { mount, tempdir: Some(tempdir) }
+    }
+}
+
+impl<'a> Drop for MountGuard<'a> {
+    fn drop(&mut self) {
+        let tempdir = match self.tempdir.take() {
+            Some(tempdir) => tempdir,
+            None => return,
+        };
+
+        // A lazy unmount is only needed if the mount is still busy.
+        let unmounted = self
+            .mount
+            .unmount(UnmountFlags::empty())
+            .or_else(|_| self.mount.unmount(UnmountFlags::DETACH));
+
+        match unmounted {
+            Ok(()) => {
+                if let Err(why) = tempdir.close() {
+                    warn!("unable to remove a temporary mount directory: {}", why);
+                }
+            }
+            Err(why) => {
+                let path = tempdir.into_path();
+                warn!("unable to unmount {:?}, so it will be left behind: {}", path, why);
+            }
+        }
+    }
+}
+
+/// Identifies a device by its UUID, or its PARTUUID if it has no UUID.
+fn device_id(device: &Path) -> Option<PartitionID> {
+    PartitionID::get_uuid(device).or_else(|| PartitionID::get_partuuid(device))
+}
+
+/// Records the partition that a Linux install was found on.
+fn set_root(os: &mut OS, id: Option<PartitionID>) {
+    if let OS::Linux(LinuxInstall { ref mut root, .. }) = *os {
+        *root = id;
+    }
+}
+
+/// Records the `device` that a Linux install was found on, and looks up the identifiers
+/// of it and each partition in its fstab among this system's devices.
+fn set_root_device(os: &mut OS, device: &Path) {
+    set_root(os, device_id(device));
+    complete_part_refs(os, device, PartitionID::get_device_path, |path| {
+        PartitionIdentifiers::from_path(path)
+    });
+}
+
+/// Completes the `part_refs` of a Linux install found on `device`, finding the device of
+/// each partition with `resolve`, and then its identifiers with `identify`.
+fn complete_part_refs<R, I>(os: &mut OS, device: &Path, resolve: R, identify: I)
+where
+    R: Fn(&PartitionID) -> Option<PathBuf>,
+    I: Fn(&Path) -> PartitionIdentifiers,
+{
+    let (part_refs, partitions, targets) = match *os {
+        OS::Linux(LinuxInstall { ref mut part_refs, ref partitions, ref targets, .. }) => {
+            (part_refs, partitions, targets)
+        }
+        _ => return,
+    };
+
+    for (id, target) in partitions.iter().zip(targets) {
+        if let Some(path) = resolve(id) {
+            part_refs.entry(target.clone()).or_default().complete(identify(&path));
+        }
+    }
+
+    part_refs.entry(PathBuf::from("/")).or_default().complete(identify(device));
+}
+
+/// Like `try_detect_os_from_device`, but gives up if the device could not be mounted and
+/// probed within the given `timeout`, as can happen with flaky USB or network-backed media.
+///
+/// See `Detector::timeout` for how the probe is cleaned up after a timeout.
+pub fn detect_os_from_device_timeout<'a, F: Into<FilesystemType<'a>>>(
+    device: &Path,
+    fs: F,
+    timeout: Duration,
+) -> Result<Option<OS>, DetectError> {
+    Detector::default().timeout(Some(timeout)).detect(device, fs)
+}
+
+/// An owned equivalent of `FilesystemType`, which can be moved to a worker thread.
+enum OwnedFilesystemType {
+    Auto,
+    Manual(String),
+    Set(Vec<String>),
+}
+
+impl<'a, 'b> From<&'b FilesystemType<'a>> for OwnedFilesystemType {
+    fn from(fs: &'b FilesystemType<'a>) -> Self {
+        match *fs {
+            FilesystemType::Auto(_) => OwnedFilesystemType::Auto,
+            FilesystemType::Manual(fs) => OwnedFilesystemType::Manual(fs.into()),
+            FilesystemType::Set(set) => {
+                OwnedFilesystemType::Set(set.iter().map(|&fs| fs.into()).collect())
+            }
+        }
+    }
+}
+
+impl OwnedFilesystemType {
+    /// Lends a `FilesystemType` borrowing from this to `func`.
+    fn with<T, F>(&self, func: F) -> Result<T, DetectError>
+    where
+        F: FnOnce(FilesystemType) -> Result<T, DetectError>,
+    {
+        match *self {
+            OwnedFilesystemType::Auto => {
+                // The list of supported filesystems is fetched again, as it can't be shared.
+                let supported = SupportedFilesystems::new().map_err(DetectError::Io)?;
+                func(FilesystemType::Auto(&supported))
+            }
+            OwnedFilesystemType::Manual(ref fs) => func(FilesystemType::Manual(fs)),
+            OwnedFilesystemType::Set(ref set) => {
+                let set: Vec<&str> = set.iter().map(String::as_str).collect();
+                func(FilesystemType::Set(&set))
+            }
+        }
+    }
+}
+
+/// Runs `task` on a worker thread, returning `None` if it doesn't finish within `timeout`.
+///
+/// The worker is detached on timeout, and its result is discarded when it finishes.
+fn run_with_timeout<T, F>(timeout: Duration, task: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        // The receiver is gone if it timed out, in which case the result is dropped here.
+        let _ = tx.send(task());
+    });
+
+    rx.recv_timeout(timeout).ok()
+}
+
+/// How many devices `detect_os_from_devices` probes at a time.
+const PARALLEL_WORKERS: usize = 4;
+
+/// Maps each item with `task` on up to `workers` threads, keeping the order of `items`.
+fn run_parallel<T, R, F>(items: &[T], workers: usize, task: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers.min(items.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, AtomicOrdering::Relaxed);
+                        match items.get(index) {
+                            Some(item) => results.push((index, task(item))),
+                            None => return results,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+
+    results.sort_by_key(|&(index, _)| index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// The filesystems that detection is known to work with, by the names they're mounted by.
+///
+/// These are also the filesystems tried by `detect_os_from_device_auto`, in order.
+pub const SUPPORTED_FILESYSTEMS: &[&str] = &[
+    "ext4", "ext3", "ext2", "btrfs", "xfs", "f2fs", "ntfs", "ntfs3", "vfat", "exfat", "hfsplus",
+    "apfs",
+];
+
+/// Checks for an installed OS on a device whose filesystem type is not known.
+///
+/// The filesystem found by `probe_filesystem` is tried first. Otherwise, each of the
+/// `SUPPORTED_FILESYSTEMS` is tried in turn, until one mounts and has an OS on it. This
+/// spares the caller from having to probe the filesystem beforehand.
+pub fn detect_os_from_device_auto(device: &Path) -> Option<OS> {
+    let probed = probe_filesystem(device);
+    let candidates = auto_candidates(probed.as_deref());
+    detect_with_candidates(&candidates, |fs| try_detect_os_from_device(device, fs))
+}
+
+/// The filesystems to try, in order, for a device that was probed as `probed`.
+fn auto_candidates(probed: Option<&str>) -> Vec<&str> {
+    let mut candidates: Vec<&str> = probed.into_iter().collect();
+    candidates.extend(SUPPORTED_FILESYSTEMS.iter().filter(|&&fs| probed != Some(fs)));
+    candidates
+}
+
+/// Identifies the filesystem of a device from its superblock, without mounting it.
+///
+/// The name is the one that the filesystem is mounted by, such as `ext4` or `vfat`.
+/// `None` is returned if the device can't be read, or its filesystem isn't recognized.
+pub fn probe_filesystem(device: &Path) -> Option<String> {
+    probe_filesystem_at(device, 0)
+}
+
+/// Like `probe_filesystem`, for a filesystem which starts at `offset` within the device.
+fn probe_filesystem_at(device: &Path, offset: u64) -> Option<String> {
+    let mut file = open(device).ok()?;
+    match file.seek(SeekFrom::Start(offset)).and_then(|_| superblock::probe(file)) {
+        Ok(fs) => {
+            debug!("probed {:?} as {:?}", device, fs);
+            fs.map(String::from)
+        }
+        Err(why) => {
+            debug!("unable to probe the filesystem of {:?}: {}", device, why);
+            None
+        }
+    }
+}
+
+/// Checks whether an ext2, ext3, or ext4 filesystem needs to be checked with `fsck` before
+/// it's mounted read-write, from the state and features in its superblock, without
+/// mounting it.
+///
+/// It does if it wasn't unmounted cleanly, errors were recorded in it, or its journal has
+/// transactions to replay. An error of kind `InvalidData` is returned if the device doesn't
+/// hold an ext filesystem.
+pub fn ext4_needs_fsck(device: &Path) -> io::Result<bool> {
+    let mut superblock = Vec::with_capacity(superblock::EXT_PROBE_LEN as usize);
+    open(device)?.take(superblock::EXT_PROBE_LEN).read_to_end(&mut superblock)?;
+    superblock::ext_needs_fsck(&superblock).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not an ext filesystem", device))
+    })
+}
+
+/// Returns the first detection which succeeds among the candidate filesystems.
+fn detect_with_candidates<F>(candidates: &[&str], mut probe: F) -> Option<OS>
+where
+    F: FnMut(&str) -> Result<Option<OS>, DetectError>,
+{
+    candidates.iter().find_map(|fs| match probe(fs) {
+        Ok(os) => os,
+        Err(why) => {
+            debug!("unable to probe with {}: {:?}", fs, why);
+            None
+        }
+    })
+}
+
+/// Flags to mount a device with for probing, in the order that they are attempted.
+///
+/// Probing should never modify the volume, so a read-only mount is always tried first.
+/// Some drivers refuse to mount read-only without extra options, in which case the
+/// device is mounted read-write as a last resort.
+const PROBE_MOUNT_FLAGS: [MountFlags; 2] = [MountFlags::RDONLY, MountFlags::empty()];
+
+fn mount_probe(
+    mounter: &dyn Mounter,
+    device: &Path,
+    target: &Path,
+    fs: &FilesystemType,
+    read_only: bool,
+    extra: MountFlags,
+    data: Option<&str>,
+) -> io::Result<Box<dyn Unmount>> {
+    let drivers = filesystem_drivers(fs);
+    let mount = |fs: &FilesystemType, flags: MountFlags| -> io::Result<Box<dyn Unmount>> {
+        let name = filesystem_name(fs);
+        trace!("mounting {:?} as {} with {:?}", device, name, flags | extra);
+        let mount = mounter.mount(device, target, fs, flags | extra, data)?;
+        if drivers.len() > 1 {
+            debug!("mounted {:?} with the {} driver", device, name);
+        }
+
+        Ok(mount)
+    };
+
+    let mount_any = |flags: MountFlags| -> io::Result<Box<dyn Unmount>> {
+        let (last, others) = drivers.split_last().expect("no filesystem drivers to attempt");
+        for fs in others {
+            match mount(fs, flags) {
+                Ok(mount) => return Ok(mount),
+                Err(why) => {
+                    debug!("unable to mount {:?} as {}: {}", device, filesystem_name(fs), why)
+                }
+            }
+        }
+
+        mount(last, flags)
+    };
+
+    // Every driver is tried read-only before any is tried read-write.
+    let attempts = mount_attempts(read_only);
+    let (&last, attempts) = attempts.split_last().expect("no mount flags to attempt");
+    for &flags in attempts {
+        match mount_any(flags) {
+            Ok(mount) => return Ok(mount),
+            Err(why) => {
+                warn!("unable to mount {:?} read-only ({}); mounting read-write", device, why)
+            }
+        }
+    }
+
+    mount_any(last)
+}
+
+/// The flags that a device is mounted with, in the order that they're attempted.
+fn mount_attempts(read_only: bool) -> &'static [MountFlags] {
+    if read_only {
+        &PROBE_MOUNT_FLAGS[..]
+    } else {
+        &PROBE_MOUNT_FLAGS[1..]
+    }
+}
+
+/// The filesystem types to mount a device with, in the order that they're attempted.
+fn filesystem_drivers<'a>(fs: &FilesystemType<'a>) -> Vec<FilesystemType<'a>> {
+    match *fs {
+        FilesystemType::Manual(fs) => {
+            filesystem_aliases(fs).into_iter().map(FilesystemType::Manual).collect()
+        }
+        _ => vec![copy_fs(fs)],
+    }
+}
+
+/// The drivers which may mount a filesystem, in order of preference. Kernels may have
+/// either of the NTFS drivers, and `msdos` lacks long file names, so is tried last.
+fn filesystem_aliases(fs: &str) -> Vec<&str> {
+    match fs {
+        "ntfs" | "ntfs3" => vec!["ntfs3", "ntfs"],
+        "vfat" => vec!["vfat", "msdos"],
+        _ => vec![fs],
+    }
+}
+
+/// Whether a filesystem that a device is mounted as is the one that its superblock was
+/// probed as, including by another of its drivers: the ext4 driver mounts ext2 and ext3,
+/// and `msdos` mounts `vfat`.
+fn same_filesystem(declared: &str, probed: &str) -> bool {
+    let family = |fs| match fs {
+        "ext2" | "ext3" | "ext4" => "ext",
+        "ntfs3" => "ntfs",
+        "msdos" | "fat" => "vfat",
+        fs => fs,
+    };
+
+    family(declared) == family(probed)
+}
+
+/// `FilesystemType` only holds references, but doesn't implement `Clone`.
+#[allow(clippy::needless_match)]
+fn copy_fs<'a>(fs: &FilesystemType<'a>) -> FilesystemType<'a> {
+    match *fs {
+        FilesystemType::Auto(supported) => FilesystemType::Auto(supported),
+        FilesystemType::Manual(fs) => FilesystemType::Manual(fs),
+        FilesystemType::Set(set) => FilesystemType::Set(set),
+    }
+}
+
+/// A function which checks a path for the signature of an OS.
+type Detect = fn(&Path) -> Option<OS>;
+
+/// The detectors which are run against a path, by name, in order of precedence.
+const DETECTORS: &[(OsKind, &str, Detect)] = &[
+    (OsKind::Bsd, "BSD", detect_bsd),
+    (OsKind::Linux, "Linux", detect_linux),
+    (OsKind::ChromeOs, "ChromeOS", detect_chromeos),
+    (OsKind::Android, "Android", detect_android),
+    (OsKind::Windows, "Windows", detect_windows),
+    (OsKind::MacOs, "macOS", detect_macos),
+];
+
+/// Detects an OS at a path with only the detectors of the given kinds, tried in the order
+/// given, such as when the kind is expected from the partition's GPT type.
+///
+/// This spares the files that the other detectors would read. Unlike
+/// `detect_os_from_path`, an `OS::Unknown` is never returned.
+pub fn detect_specific(base: &Path, which: &[OsKind]) -> Option<OS> {
+    which.iter().find_map(|&kind| {
+        let &(_, name, detect) = DETECTORS.iter().find(|&&(known, ..)| known == kind)?;
+        trace!("checking {:?} for {}", base, name);
+        detect(base)
+    })
+}
+
+/// Detects the existence of an OS at a defined path.
+///
+/// If no OS is detected, but the path has some of the files of one, such as an `etc`
+/// directory without an os-release, an `OS::Unknown` listing them is returned instead.
+///
+/// This is the entry point for a directory which is already available, such as the
+/// extracted contents of a disk image, and is called by `detect_os_from_device` after
+/// having temporarily mounted a device. With no device behind the path, the `root` of a
+/// Linux install is left as `None`; see `detect_os_at_mountpoint` to supply one.
+pub fn detect_os_from_path(base: &Path) -> Option<OS> {
+    detect_all(base).into_iter().next().or_else(|| detect_unknown(base))
+}
+
+/// Finds the name and version of the OS that `detect_os_from_path` would detect, for
+/// callers which need nothing else.
+///
+/// A Linux install is summarized from its os-release alone, without reading the fstab or
+/// anything else that `detect_linux` inspects. An os-release which `detect_linux` would
+/// reject as corrupt isn't summarized either. Other OSes are detected in full. Partial signatures, from which `detect_os_from_path`
+/// returns an `OS::Unknown`, aren't summarized.
+pub fn detect_os_summary(base: &Path) -> Option<OsSummary> {
+    let builtin = DETECTORS.iter().find_map(|&(kind, _, detect)| match kind {
+        OsKind::Linux => summarize_linux(base),
+        _ => detect(base).as_ref().and_then(OsSummary::of),
+    });
+
+    builtin.or_else(|| {
+        let custom = custom_detectors().lock().unwrap_or_else(|why| why.into_inner()).clone();
+        custom.iter().find_map(|detect| detect(base).as_ref().and_then(OsSummary::of))
+    })
+}
+
+/// Summarizes a Linux install from the name and version of its os-release, after any of
+/// the `REFINEMENTS`.
+fn summarize_linux(base: &Path) -> Option<OsSummary> {
+    if is_chromeos(base) {
+        return None;
+    }
+
+    let path = match find_os_release(base) {
+        Some((path, _)) => path,
+        // An ostree deployment or initramfs is rare enough to detect in full.
+        None => return detect_linux(base).as_ref().and_then(OsSummary::of),
+    };
+
+    let (mut info, _) = match fs::read(&path).and_then(|release| parse_os_release(&release)) {
+        Ok(release) => release,
+        Err(why) => {
+            debug!("not summarizing {:?}: {}", path, why);
+            return None;
+        }
+    };
+
+    REFINEMENTS.iter().any(|&(_, refine)| refine(base, &mut info));
+    let name = linux_name(&info).to_owned();
+    Some(OsSummary { kind: OsKind::Linux, name, version: linux_version(&info) })
+}
+
+/// Detects the existence of an OS at a path within a mounted volume, such as the `@`
+/// subvolume of a btrfs filesystem, or an ostree deployment.
+///
+/// The `subpath` is relative to `mount`, even if it starts with a `/`. Nothing is detected
+/// if it leads outside of `mount`, with a `..` or through a symbolic link.
+pub fn detect_os_from_path_prefixed(mount: &Path, subpath: &Path) -> Option<OS> {
+    match join_within(mount, subpath) {
+        Some(base) => detect_os_from_path(&base),
+        None => {
+            warn!("refusing to probe {:?}, which leads outside of {:?}", subpath, mount);
+            None
+        }
+    }
+}
+
+/// Joins a path to a base directory, unless the result would be outside of it.
+fn join_within(base: &Path, path: &Path) -> Option<PathBuf> {
+    let mut joined = base.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => joined.push(name),
+            Component::CurDir | Component::RootDir => (),
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    // A symbolic link along the way may still lead elsewhere.
+    match (joined.canonicalize(), base.canonicalize()) {
+        (Ok(resolved), Ok(base)) if !resolved.starts_with(&base) => None,
+        _ => Some(joined),
+    }
+}
+
+/// Files and directories which hint at an OS, but don't identify one by themselves.
+const PARTIAL_SIGNATURES: &[&str] = &[
+    "etc",
+    "etc/os-release",
+    "usr",
+    "boot",
+    "Boot",
+    "Windows",
+    "Program Files",
+    "System/Library",
+    "bootmgr",
+    "system/build.prop",
+];
+
+/// Lists the partial signatures of an OS at a path, if there are any. Dangling symlinks
+/// count, as they're still a sign of what was installed.
+fn detect_unknown(base: &Path) -> Option<OS> {
+    let hints: Vec<String> = PARTIAL_SIGNATURES
+        .iter()
+        .filter_map(|&path| {
+            let full = base.join(path);
+            match fs::metadata(&full).or_else(|_| fs::symlink_metadata(&full)) {
+                Ok(ref metadata) if metadata.is_dir() => Some(format!("{}/", path)),
+                Ok(_) => Some(path.to_owned()),
+                Err(_) => None,
+            }
+        })
+        .collect();
+
+    if hints.is_empty() {
+        return None;
+    }
+
+    debug!("found partial signatures of an OS at {:?}: {:?}", base, hints);
+    Some(OS::Unknown { hints })
+}
+
+/// Detects the existence of an OS on a device which is already mounted at `mount`, such as
+/// a partition that is in the middle of being installed to.
+///
+/// Mounting a device that's already mounted, as `detect_os_from_device` would, may fail or
+/// expose it twice, so this should be used instead. The `device` backing the mount may be
+/// supplied to have it recorded as the `root` of a Linux install. For a plain directory
+/// with no backing device, `detect_os_from_path` is equivalent.
+pub fn detect_os_at_mountpoint(mount: &Path, device: Option<&Path>) -> Option<OS> {
+    let mut os = detect_os_from_path(mount)?;
+    if let Some(device) = device {
+        set_root_device(&mut os, device);
+    }
+
+    Some(os)
+}
+
+/// Detects the Linux installs on each top-level subvolume of a btrfs filesystem, such as
+/// several distributions, or snapshots of one, which share a partition.
+///
+/// The filesystem must be mounted at `mount` with its top-level subvolume (`subvolid=5`),
+/// so that each subvolume is reachable at its path. They're listed by
+/// `btrfs subvolume list`, so nothing is found if `btrfs-progs` isn't installed.
+pub fn detect_btrfs_roots(mount: &Path) -> Vec<OS> {
+    let output = match Command::new("btrfs").args(["subvolume", "list"]).arg(mount).output() {
+        Ok(ref output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Ok(output) => {
+            let why = String::from_utf8_lossy(&output.stderr);
+            warn!("unable to list the subvolumes of {:?}: {}", mount, why.trim());
+            return Vec::new();
+        }
+        Err(why) => {
+            warn!("unable to run btrfs to list the subvolumes of {:?}: {}", mount, why);
+            return Vec::new();
+        }
+    };
+
+    parse_subvolume_list(&output)
+        .into_iter()
+        .filter_map(|subvolume| {
+            trace!("checking subvolume {} of {:?}", subvolume, mount);
+            detect_linux(&mount.join(subvolume))
+        })
+        .collect()
+}
+
+/// The subvolume ID of the top-level subvolume, which all others descend from.
+const BTRFS_TOP_LEVEL: u64 = 5;
+
+/// Parses the paths of the top-level subvolumes from the output of `btrfs subvolume list`,
+/// whose lines are in the form `ID 256 gen 1024 top level 5 path @`.
+fn parse_subvolume_list(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (fields, path) = line.split_once(" path ")?;
+            let top_level = fields.split_once(" top level ")?.1.trim().parse::<u64>().ok()?;
+            (top_level == BTRFS_TOP_LEVEL).then_some(path.trim())
+        })
+        .collect()
+}
+
+/// Detects every OS with a signature in a directory, such as an extracted disk image with
+/// more than one OS in its tree.
+///
+/// This is the directory counterpart of `detect_os_from_path`, and is equivalent to
+/// `detect_all`.
+pub fn detect_os_from_path_all(base: &Path) -> Vec<OS> {
+    detect_all(base)
+}
+
+/// Like `detect_os_from_path`, but also reports how complete the detected install is, and
+/// which files it was detected from, for auditing why a partition was classified as it
+/// was.
+pub fn detect_detailed(base: &Path) -> Option<Detection> {
+    let os = detect_os_from_path(base)?;
+    let mut evidence: Vec<String> = signature_files(&os)
+        .iter()
+        .filter_map(|file| {
+            let path = join_case_insensitive(base, file);
+            path.symlink_metadata().ok()?;
+            Some(path.strip_prefix(base).ok()?.to_string_lossy().into_owned())
+        })
+        .collect();
+
+    evidence.extend(signature_keys(&os));
+    Some(Detection { confidence: confidence(&os), os, evidence })
+}
+
+/// How complete a detected install is.
+fn confidence(os: &OS) -> Confidence {
+    match *os {
+        OS::Linux(LinuxInstall { is_bootable: false, .. }) => Confidence::Container,
+        OS::Windows { boot_manager: true, .. } => Confidence::BootOnly,
+        OS::MacOs { ref version, .. } if version.is_empty() => Confidence::Partial,
+        OS::Unknown { .. } => Confidence::Partial,
+        _ => Confidence::Full,
+    }
+}
+
+/// The files that an OS of the same kind is detected from, when they exist.
+fn signature_files(os: &OS) -> &'static [&'static str] {
+    match *os {
+        OS::Linux(LinuxInstall { .. }) => &[
+            "etc/os-release",
+            "usr/lib/os-release",
+            "etc/fstab",
+            "lib/systemd/systemd",
+            "usr/lib/systemd/systemd",
+            "sbin/openrc",
+            "sbin/init",
+        ],
+        OS::Windows { boot_manager: true, .. } => &["bootmgr", "Boot/BCD"],
+        OS::Windows { .. } => &[
+            "Windows/System32/ntoskrnl.exe",
+            "Windows/System32/config/SOFTWARE",
+            "Windows/SysWOW64",
+            "hiberfil.sys",
+            "pagefile.sys",
+        ],
+        OS::MacOs { .. } => &[
+            "System/Library/CoreServices/SystemVersion.plist",
+            "System/Library/CoreServices/ServerVersion.plist",
+            "private/var",
+            "Users",
+        ],
+        OS::ChromeOs(_) => &["etc/lsb-release"],
+        OS::Android(_) => &["system/build.prop", "build.prop"],
+        OS::Bsd { .. } => &["etc/rc.conf", "bin/freebsd-version", "bsd", "netbsd"],
+        OS::Unknown { .. } => &[],
+    }
+}
+
+/// The keys read from the files of an OS which identified it, as `KEY=value`.
+fn signature_keys(os: &OS) -> Vec<String> {
+    match *os {
+        OS::Linux(LinuxInstall { ref info, .. }) if !info.id.is_empty() => {
+            vec![format!("ID={}", info.id)]
+        }
+        OS::Windows { ref product_name, build: Some(build), .. } => {
+            vec![format!("ProductName={}", product_name), format!("CurrentBuild={}", build)]
+        }
+        OS::MacOs { ref version, .. } if !version.is_empty() => {
+            vec![format!("ProductUserVisibleVersion={}", version)]
+        }
+        OS::Unknown { ref hints } => hints.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// A detector for an OS that isn't built in, which can be added with `register_detector`.
+pub type CustomDetector = dyn Fn(&Path) -> Option<OS> + Send + Sync;
+
+fn custom_detectors() -> &'static Mutex<Vec<Arc<CustomDetector>>> {
+    static CUSTOM_DETECTORS: OnceLock<Mutex<Vec<Arc<CustomDetector>>>> = OnceLock::new();
+    CUSTOM_DETECTORS.get_or_init(Mutex::default)
+}
+
+/// Registers a detector for an OS that isn't built in, such as Haiku or ReactOS, to be run
+/// by `detect_os_from_path`, `detect_all`, and the detection of devices.
+///
+/// Registered detectors run after the built-in ones, in the order that they were
+/// registered, and stay registered for the life of the process.
+///
+/// ```rust
+/// extern crate os_detect;
+///
+/// use os_detect::{register_detector, OS};
+///
+/// pub fn main() {
+///     register_detector(Box::new(|base| {
+///         let hints = vec!["boot/system".to_owned()];
+///         base.join("boot/system/haiku_loader.bios_ia32").exists().then(|| OS::Unknown { hints })
+///     }));
+/// }
+/// ```
+pub fn register_detector(detector: Box<CustomDetector>) {
+    let mut detectors = custom_detectors().lock().unwrap_or_else(|why| why.into_inner());
+    detectors.push(Arc::from(detector));
+}
+
+/// Detects every OS with a signature at a defined path.
+///
+/// A single volume may carry the signatures of more than one OS, such as a Windows volume
+/// with leftover Linux files. The results are in the same order of precedence as is used
+/// by `detect_os_from_path`.
+pub fn detect_all(base: &Path) -> Vec<OS> {
+    let mut found: Vec<OS> = DETECTORS
+        .iter()
+        .filter_map(|&(_, name, detect)| {
+            trace!("checking {:?} for {}", base, name);
+            let os = detect(base);
+            if let Some(ref os) = os {
+                debug!("found {} at {:?}: {:?}", name, base, os);
+            }
+
+            os
+        })
+        .collect();
+
+    // The registry isn't locked while they run, so that they may detect or register too.
+    let custom = custom_detectors().lock().unwrap_or_else(|why| why.into_inner()).clone();
+    for detect in custom {
+        if let Some(os) = detect(base) {
+            debug!("found {} at {:?} with a registered detector: {:?}", os.name(), base, os);
+            found.push(os);
+        }
+    }
+
+    if found.is_empty() {
+        debug!("found no OS at {:?}", base);
+    }
+
+    found
+}
+
+/// Detect if Linux is installed at the given path.
+///
+/// An unreadable or corrupt `/etc/os-release` is treated as no OS being installed. Use
+/// `try_detect_linux` to tell these cases apart. Nor is ChromeOS detected here, despite
+/// its os-release, since `detect_chromeos` describes it better from its lsb-release.
+pub fn detect_linux(base: &Path) -> Option<OS> {
+    try_detect_linux(base).ok().flatten()
+}
+
+/// Detect if Linux is installed at the given path, with an error if its `/etc/os-release`
+/// exists but couldn't be read or parsed, or is a symlink to an os-release that doesn't
+/// exist, as may be the case on a damaged system.
+pub fn try_detect_linux(base: &Path) -> Result<Option<OS>, DetectError> {
+    detect_linux_with_resolver(base, |_| None)
+}
+
+/// Like `detect_linux`, but with a `resolve` callback which maps a partition of the
+/// install's fstab to the path it's currently mounted at, so that its contents can be
+/// inspected too. The bootloader is read from the EFI partition this way, rather than
+/// from `/boot/efi` within `base`, which is only populated if the partition is mounted
+/// there. The recovery partition is read with `detect_recovery`, for `recovery_image`.
+///
+/// The callback should return `None` for a partition which isn't mounted, in which case
+/// detection falls back to what `detect_linux` finds. It's only asked about partitions
+/// listed in the fstab, and must not mount anything itself.
+///
+/// As with `try_detect_linux`, a corrupt `/etc/os-release` is reported as an error.
+pub fn detect_linux_with_resolver<F>(base: &Path, resolve: F) -> Result<Option<OS>, DetectError>
+where
+    F: Fn(&PartitionID) -> Option<PathBuf>,
+{
+    if is_chromeos(base) {
+        return Ok(None);
+    }
+
+    let (path, release, source) = match find_os_release(base) {
+        Some((path, source)) => {
+            trace!("found {:?}", path);
+            match fs::read(&path) {
+                Ok(release) => (path, release, source),
+                Err(why) => return Err(DetectError::OsRelease { path, why }),
+            }
+        }
+        None => {
+            if let Some(deployment) = ostree_deployment(base) {
+                let mut os = detect_linux_with_resolver(&deployment, resolve)?;
+                if let Some(OS::Linux(LinuxInstall { ref mut ostree, .. })) = os {
+                    *ostree = true;
+                }
+
+                return Ok(os);
+            }
+
+            match initramfs_os_release(base) {
+                Some((image, release)) => (image, release, OsReleaseSource::Initramfs),
+                None => match broken_os_release(base) {
+                    Some((path, why)) => return Err(DetectError::OsRelease { path, why }),
+                    None => return Ok(None),
+                },
+            }
+        }
+    };
+
+    let (mut info, raw) =
+        parse_os_release(&release).map_err(|why| DetectError::OsRelease { path, why })?;
+    if let Some(&(name, _)) = REFINEMENTS.iter().find(|&&(_, refine)| refine(base, &mut info)) {
+        debug!("refined {:?} to {}", base, name);
+    }
+
+    let fstab = find_linux_parts(base);
+    let init_system = detect_init_system(base);
+    let has_kernel = has_kernel_image(base) || fstab.find("/boot").is_some();
+    let is_bootable = init_system.is_some() && has_kernel;
+    let recovery = fstab.mount_source("/recovery");
+    let recovery_image = match recovery {
+        Some(MountSource::Partition { ref id, .. }) => {
+            resolve(id).and_then(|path| detect_recovery(&path))
+        }
+        _ => None,
+    };
+
+    let efi = fstab.find("/boot/efi").map(|entry| entry.source.clone());
+    let bootloader = efi
+        .as_ref()
+        .and_then(&resolve)
+        .and_then(|path| detect_bootloader(&path))
+        .or_else(|| detect_bootloader(&base.join("boot/efi")));
+    let boot_mode = detect_boot_mode(base, efi.is_some());
+    let usr = fstab.find("/usr").map(|entry| entry.source.clone());
+    let usr_separate = usr.is_some();
+    let boot = fstab.find("/boot").map(|entry| entry.source.clone());
+    let grub_default = boot
+        .as_ref()
+        .and_then(&resolve)
+        .and_then(|path| read_grub_menu(&path))
+        .or_else(|| read_grub_menu(&base.join("boot")))
+        .and_then(|menu| match menu.default_entry() {
+            Some(entry) => Some(entry.title.clone()),
+            None => menu.default,
+        });
+
+    let id_like = info.id_like.split_whitespace().map(String::from).collect();
+    Ok(Some(OS::Linux(LinuxInstall {
+        info,
+        source,
+        variant: raw.get("VARIANT").cloned(),
+        variant_id: raw.get("VARIANT_ID").cloned(),
+        id_like,
+        ansi_color: raw.get("ANSI_COLOR").cloned(),
+        logo: raw.get("LOGO").cloned(),
+        raw,
+        home: fstab.mount_source("/home"),
+        efi,
+        boot,
+        usr,
+        usr_separate,
+        recovery,
+        recovery_valid: recovery_image.is_some(),
+        recovery_image,
+        var: fstab.mount_source("/var"),
+        machine_id: read_machine_id(base),
+        hostname: read_hostname(base),
+        network_config: find_network_config(base),
+        timezone: read_timezone(base),
+        locale: read_locale(base),
+        last_seen: LastSeen(last_seen(base)),
+        kernels: find_kernels(base),
+        root: None,
+        root_encrypted: fstab.find("/").is_some_and(|entry| entry.encrypted),
+        root_fs: fstab.find("/").map(|entry| entry.fs.clone()).filter(|fs| fs != "auto"),
+        swap: fstab.swap(),
+        swapfile: fstab.swapfile(),
+        bootloader,
+        grub_default,
+        boot_mode,
+        arch: detect_elf_arch(base),
+        init_system,
+        security_module: detect_security_module(base),
+        package_manager: detect_package_manager(base),
+        desktop: detect_desktop(base),
+        is_bootable,
+        ostree: false,
+        partitions: fstab.entries.iter().map(|entry| entry.source.clone()).collect(),
+        part_refs: fstab
+            .entries
+            .iter()
+            .map(|entry| (entry.target.clone(), PartRef::from_id(&entry.source)))
+            .collect(),
+        targets: fstab.entries.into_iter().map(|entry| entry.target).collect(),
+    })))
+}
+
+/// Rewrites the os-release details of a generic Linux detection if the install at `base`
+/// turns out to be a more specific distribution, returning whether it did.
+type Refinement = fn(&Path, &mut OsRelease) -> bool;
+
+/// Distributions which ship the os-release of the one they're built on, and are only told
+/// apart by their layout, by their `ID`.
+const REFINEMENTS: &[(&str, Refinement)] = &[("qubes", refine_qubes)];
+
+/// The dom0 of Qubes OS, which otherwise reports itself as the Fedora it's built on, is
+/// recognized by its `/etc/qubes` directory. Its version is read from the
+/// `/etc/qubes-release`, such as `Qubes release 4.1.2 (R4.1)`.
+fn refine_qubes(base: &Path, info: &mut OsRelease) -> bool {
+    if !base.join("etc/qubes").is_dir() {
+        return false;
+    }
+
+    let release = fs::read_to_string(base.join("etc/qubes-release")).unwrap_or_default();
+    let version = release.lines().next().and_then(|line| line.split_once("release "));
+    let version = version.map_or("", |(_, version)| version.trim());
+    refine_as(info, "qubes", "Qubes OS", version);
+    true
+}
+
+/// Relabels an os-release as that of the distribution `id`, which derives from the one it
+/// names, so that `OS::is_like` still matches the original.
+fn refine_as(info: &mut OsRelease, id: &str, name: &str, version: &str) {
+    if info.id != id {
+        info.id_like = format!("{} {}", info.id, info.id_like).trim().to_owned();
+        info.id = id.into();
+    }
+
+    info.name = name.into();
+    info.version = version.into();
+    info.version_id = version.split_whitespace().next().unwrap_or("").into();
+    info.version_codename.clear();
+    info.pretty_name = format!("{} {}", name, version).trim().to_owned();
+}
+
+/// Desktops by the binary that starts their session, and the display manager that they
+/// ship with, if any.
+const DESKTOPS: &[(&str, &str, Option<&str>)] = &[
+    ("GNOME", "usr/bin/gnome-session", Some("gdm")),
+    ("KDE Plasma", "usr/bin/startplasma-x11", Some("sddm")),
+    ("KDE Plasma", "usr/bin/startplasma-wayland", Some("sddm")),
+    ("COSMIC", "usr/bin/cosmic-session", Some("cosmic-greeter")),
+    ("Xfce", "usr/bin/startxfce4", None),
+    ("Cinnamon", "usr/bin/cinnamon-session", None),
+    ("MATE", "usr/bin/mate-session", None),
+    ("LXQt", "usr/bin/startlxqt", None),
+    ("Budgie", "usr/bin/budgie-desktop", None),
+];
+
+/// Detects the desktop environment from the sessions that are installed. If there's more
+/// than one, the desktop that the enabled display manager belongs to is chosen, if any.
+fn detect_desktop(base: &Path) -> Option<String> {
+    let mut installed: Vec<_> =
+        DESKTOPS.iter().filter(|desktop| base.join(desktop.1).exists()).collect();
+    installed.dedup_by_key(|desktop| desktop.0);
+
+    if installed.len() > 1 {
+        let link = fs::read_link(base.join("etc/systemd/system/display-manager.service")).ok()?;
+        let manager = link.file_stem()?.to_str()?;
+        installed.retain(|desktop| desktop.2 == Some(manager));
+    }
+
+    match installed.as_slice() {
+        [desktop] => Some(desktop.0.into()),
+        _ => None,
+    }
+}
+
+/// Infers the boot mode from an EFI system partition in the fstab, and GRUB's BIOS modules.
+fn detect_boot_mode(base: &Path, has_efi: bool) -> BootMode {
+    let bios =
+        ["boot/grub/i386-pc", "boot/grub2/i386-pc"].iter().any(|dir| base.join(dir).is_dir());
+    match (has_efi, bios) {
+        (true, true) => BootMode::Hybrid,
+        (true, false) => BootMode::Uefi,
+        (false, true) => BootMode::Bios,
+        (false, false) => BootMode::Unknown,
+    }
+}
+
+/// Checks for a kernel image in `/boot`, such as `vmlinuz-6.5.0`.
+fn has_kernel_image(base: &Path) -> bool {
+    const IMAGES: &[&str] = &["vmlinuz", "vmlinux", "bzImage", "kernel-"];
+    read_dir_names(&base.join("boot"))
+        .any(|name| IMAGES.iter().any(|image| name.starts_with(image)))
+}
+
+/// Detects the init system from its binaries. Installs of systemd and OpenRC may also
+/// carry scripts in `/etc/init.d`, so SysVinit is only reported when neither is found.
+fn detect_init_system(base: &Path) -> Option<InitSystem> {
+    let exists = |paths: &[&str]| paths.iter().any(|path| base.join(path).exists());
+    if exists(&["lib/systemd/systemd", "usr/lib/systemd/systemd"]) {
+        Some(InitSystem::Systemd)
+    } else if exists(&["sbin/openrc", "usr/sbin/openrc", "sbin/openrc-init"]) {
+        Some(InitSystem::OpenRc)
+    } else if base.join("etc/init.d").is_dir() && exists(&["sbin/init", "usr/sbin/init"]) {
+        Some(InitSystem::SysVinit)
+    } else {
+        None
+    }
+}
+
+/// Detects SELinux by the `SELINUX` mode of `/etc/selinux/config`, unless it's `disabled`,
+/// and otherwise AppArmor by its `/etc/apparmor` configuration and the profiles in
+/// `/etc/apparmor.d`.
+fn detect_security_module(base: &Path) -> Option<SecurityModule> {
+    if let Ok(config) = open(base.join("etc/selinux/config")) {
+        match parse_key_values(BufReader::new(config)).get("SELINUX").map(String::as_str) {
+            Some("enforcing") => return Some(SecurityModule::SeLinux { enforcing: true }),
+            Some("permissive") => return Some(SecurityModule::SeLinux { enforcing: false }),
+            _ => (),
+        }
+    }
+
+    let has_profiles = fs::read_dir(base.join("etc/apparmor.d"))
+        .into_iter()
+        .flatten()
+        .any(|entry| entry.and_then(|entry| entry.file_type()).is_ok_and(|kind| kind.is_file()));
+    if base.join("etc/apparmor").is_dir() && has_profiles {
+        return Some(SecurityModule::AppArmor);
+    }
+
+    None
+}
+
+/// The databases of each package manager. Newer RPM-based distributions keep theirs in
+/// `/usr/lib/sysimage`, with `/var/lib/rpm` as a link to it.
+const PACKAGE_DATABASES: &[(&str, PackageManager)] = &[
+    ("var/lib/dpkg/status", PackageManager::Dpkg),
+    ("var/lib/rpm", PackageManager::Rpm),
+    ("usr/lib/sysimage/rpm", PackageManager::Rpm),
+    ("var/lib/pacman", PackageManager::Pacman),
+    ("etc/apk", PackageManager::Apk),
+];
+
+fn detect_package_manager(base: &Path) -> Option<PackageManager> {
+    PACKAGE_DATABASES
+        .iter()
+        .find(|&&(path, _)| base.join(path).exists())
+        .map(|&(_, manager)| manager)
+}
+
+/// Reads the recovery image on a mounted recovery partition, as found by its
+/// `recovery.conf` and the live system in a `casper` directory, which may be suffixed with
+/// its UUID.
+///
+/// The name and version of the OS are taken from the `.disk/info` of the live system, such
+/// as `Pop!_OS 22.04 LTS "Jammy Jellyfish" - Release amd64 (20230818)`, if it has one.
+pub fn detect_recovery(path: &Path) -> Option<RecoveryImage> {
+    let is_casper = |name: &str| name == "casper" || name.starts_with("casper-");
+    if !read_dir_names(path).any(|name| is_casper(&name) && path.join(&name).is_dir()) {
+        return None;
+    }
+
+    let config = parse_key_values(BufReader::new(open(path.join("recovery.conf")).ok()?));
+    trace!("found a recovery image at {:?}", path);
+    let info = fs::read_to_string(path.join(".disk/info")).unwrap_or_default();
+    let (name, version) = parse_disk_info(&info);
+    Some(RecoveryImage { name, version, config })
+}
+
+/// Splits the name and version out of the description in a live disk's `.disk/info`. The
+/// version is the first word that starts with a digit.
+fn parse_disk_info(info: &str) -> (Option<String>, Option<String>) {
+    let description = info.lines().next().unwrap_or("");
+    let description = description.split(" - ").next().unwrap_or("");
+    let words: Vec<&str> = description.split_whitespace().collect();
+    let split = words
+        .iter()
+        .position(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(words.len());
+
+    let name = Some(words[..split].join(" ")).filter(|name| !name.is_empty());
+    (name, words.get(split).map(|&version| version.to_owned()))
+}
+
+/// Locates the current deployment of an OSTree system root, whose filesystems live under
+/// `ostree/deploy/<os>/deploy/<checksum>`, rather than at the root itself.
+///
+/// The current deployment is the one that the newest boot loader entry boots, falling
+/// back to the deployment with the highest serial if there are no entries, preferring
+/// those with an `.origin` file, which every complete deployment has.
+///
+/// The `ostree=` path of a boot loader entry is taken as relative to `base`, and is ignored
+/// if it leads outside of it.
+fn ostree_deployment(base: &Path) -> Option<PathBuf> {
+    let deploy = base.join("ostree/deploy");
+    if !deploy.is_dir() {
+        return None;
+    }
+
+    trace!("found {:?}", deploy);
+    let entries = base.join("boot/loader/entries");
+    let booted = read_dir_names(&entries)
+        .filter(|name| name.ends_with(".conf"))
+        .filter_map(|name| parse_ostree_boot_entry(&fs::read_to_string(entries.join(name)).ok()?))
+        .max_by(|a, b| compare_versions(&a.0, &b.0))
+        .and_then(|(_, path)| join_within(base, Path::new(&path)))
+        .filter(|path| path.is_dir());
+
+    if let Some(booted) = booted {
+        return Some(booted);
+    }
+
+    // Deployments are named `<checksum>.<serial>`, and read from each directory in no
+    // particular order, so the path settles any ties.
+    let deployments = read_dir_names(&deploy).flat_map(|os| {
+        let os = deploy.join(os).join("deploy");
+        read_dir_names(&os).map(move |name| os.join(name)).filter(|path| path.is_dir())
+    });
+
+    deployments.min_by_key(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let serial = name.rsplit_once('.').and_then(|(_, serial)| serial.parse::<u32>().ok());
+        let origin = path.with_file_name(format!("{}.origin", name)).is_file();
+        (!origin, Reverse(serial), path.clone())
+    })
+}
+
+/// Reads the version of a boot loader entry, and the deployment that its `ostree=`
+/// kernel argument points to.
+fn parse_ostree_boot_entry(entry: &str) -> Option<(String, String)> {
+    let mut version = String::new();
+    let mut deployment = None;
+    for line in entry.lines() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("version") => version = fields.next().unwrap_or("").into(),
+            Some("options") => {
+                deployment = fields.find_map(|arg| arg.strip_prefix("ostree=")).map(String::from)
+            }
+            _ => (),
+        }
+    }
+
+    deployment.map(|deployment| (version, deployment))
+}
+
+/// Locates the os-release file, which is `/etc/os-release`, or `/usr/lib/os-release` on
+/// systems which only ship the vendor's copy.
+///
+/// `/etc/os-release` is usually a symlink to the latter.
+fn find_os_release(base: &Path) -> Option<(PathBuf, OsReleaseSource)> {
+    let etc = follow_absolute_link(base, &base.join("etc/os-release"))?;
+    if etc.exists() {
+        return Some((etc, OsReleaseSource::Etc));
+    }
+
+    let usr_lib = base.join("usr/lib/os-release");
+    usr_lib.exists().then_some((usr_lib, OsReleaseSource::UsrLib))
+}
+
+/// Finds an `/etc/os-release` which is a symlink to a file that doesn't exist, as on a
+/// damaged system, along with an error naming its target.
+fn broken_os_release(base: &Path) -> Option<(PathBuf, io::Error)> {
+    let etc = base.join("etc/os-release");
+    if !fs::symlink_metadata(&etc).ok()?.file_type().is_symlink() {
+        return None;
+    }
+
+    let target = fs::read_link(&etc).ok()?;
+    if follow_absolute_link(base, &etc)?.exists() {
+        return None;
+    }
+
+    let why = format!("dangling symlink to {}", target.display());
+    Some((etc, io::Error::new(io::ErrorKind::NotFound, why)))
+}
+
+/// Reads the os-release embedded in the newest initramfs image that has one, along with
+/// the path to that image.
+#[cfg(feature = "initramfs")]
+fn initramfs_os_release(base: &Path) -> Option<(PathBuf, Vec<u8>)> {
+    initramfs::find_images(base).into_iter().find_map(|image| {
+        let file = open(&image).ok()?;
+        match initramfs::extract_os_release(BufReader::new(file)) {
+            Ok(Some(release)) => {
+                debug!("using the os-release embedded in {:?}", image);
+                Some((image, release))
+            }
+            Ok(None) => None,
+            Err(why) => {
+                warn!("unable to read the initramfs at {:?}: {}", image, why);
+                None
+            }
+        }
+    })
+}
+
+#[cfg(not(feature = "initramfs"))]
+fn initramfs_os_release(_base: &Path) -> Option<(PathBuf, Vec<u8>)> {
+    None
+}
+
+/// Resolves a symlink with an absolute target within `base`, rather than the host.
+///
+/// Relative links already resolve within `base`, so only one level is followed.
+fn follow_absolute_link(base: &Path, path: &Path) -> Option<PathBuf> {
+    match fs::read_link(path) {
+        Ok(ref target) if target.is_absolute() => Some(base.join(target.strip_prefix("/").ok()?)),
+        _ => Some(path.to_path_buf()),
+    }
+}
+
+/// Detects the architecture of the userland from the first readable of its core binaries.
+fn detect_elf_arch(base: &Path) -> Option<Arch> {
+    const BINARIES: &[&str] = &["usr/bin/bash", "bin/bash", "sbin/init", "usr/lib/systemd/systemd"];
+    BINARIES.iter().find_map(|binary| {
+        let path = follow_absolute_link(base, &base.join(binary))?;
+        let mut header = [0; 20];
+        open(&path).ok()?.read_exact(&mut header).ok()?;
+        elf_arch(&header)
+    })
+}
+
+/// Decodes the architecture from the class and machine of an ELF header.
+fn elf_arch(header: &[u8]) -> Option<Arch> {
+    const EM_386: u16 = 3;
+    const EM_PPC64: u16 = 21;
+    const EM_S390: u16 = 22;
+    const EM_ARM: u16 = 40;
+    const EM_X86_64: u16 = 62;
+    const EM_AARCH64: u16 = 183;
+    const EM_RISCV: u16 = 243;
+
+    if header.len() < 20 || &header[..4] != b"\x7fELF" {
+        return None;
+    }
+
+    let is_64_bit = match header[4] {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+
+    let machine = match header[5] {
+        1 => u16::from_le_bytes([header[18], header[19]]),
+        2 => u16::from_be_bytes([header[18], header[19]]),
+        _ => return None,
+    };
+
+    match (machine, is_64_bit) {
+        (EM_386, false) => Some(Arch::X86),
+        (EM_X86_64, true) => Some(Arch::X86_64),
+        (EM_ARM, false) => Some(Arch::Arm),
+        (EM_AARCH64, true) => Some(Arch::Aarch64),
+        (EM_PPC64, true) => Some(Arch::PowerPc64),
+        (EM_RISCV, true) => Some(Arch::Riscv64),
+        (EM_S390, true) => Some(Arch::S390x),
+        _ => None,
+    }
+}
+
+/// Parses an os-release file, along with a map of all of its keys.
+///
+/// Lines which are neither blank, a comment, nor a `KEY=value` assignment are skipped, as
+/// a hand-edited os-release may have a stray one. It's only rejected as corrupt if it has
+/// such lines but no assignments at all, or isn't UTF-8.
+fn parse_os_release(release: &[u8]) -> io::Result<(OsRelease, BTreeMap<String, String>)> {
+    let invalid = |why: String| io::Error::new(io::ErrorKind::InvalidData, why);
+    let release = std::str::from_utf8(release).map_err(|why| invalid(why.to_string()))?;
+    let (mut stray, mut assignments) = (None, 0);
+    for (number, line) in release.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, _)) if is_os_release_key(key) => assignments += 1,
+            _ => {
+                debug!("skipping line {} of an os-release, which is not an assignment", number + 1);
+                stray.get_or_insert(number + 1);
+            }
+        }
+    }
+
+    if let (Some(number), 0) = (stray, assignments) {
+        return Err(invalid(format!("line {} is not an assignment", number)));
+    }
+
+    // `OsRelease` keeps unknown keys in `extra`, but without unquoting them.
+    let info = OsRelease::from_iter(release.lines().map(String::from));
+    Ok((info, parse_key_values(release.as_bytes())))
+}
+
+/// Whether the name of an os-release assignment is a valid shell variable name.
+fn is_os_release_key(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Unquotes the value of an os-release assignment, which follows shell quoting rules.
+///
+/// Single quotes are taken literally. Within double quotes, a backslash only escapes `"`,
+/// `\`, `$`, and `` ` ``, while outside of quotes it escapes any character. Adjacent quoted
+/// and unquoted parts are joined, and surrounding whitespace is trimmed.
+///
+/// ```rust
+/// extern crate os_detect;
+///
+/// use os_detect::unquote_os_release_value;
+///
+/// pub fn main() {
+///     assert_eq!(unquote_os_release_value(r#""Pop!_OS \"LTS\"""#), r#"Pop!_OS "LTS""#);
+///     assert_eq!(unquote_os_release_value("'C:\\Users'"), "C:\\Users");
+/// }
+/// ```
+pub fn unquote_os_release_value(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.trim().chars();
+    let mut quote = None;
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (Some('\''), _) => value.push(c),
+            (Some(_), '\\') => match chars.next() {
+                Some(escaped @ '"') | Some(escaped @ '\\') | Some(escaped @ '$')
+                | Some(escaped @ '`') => value.push(escaped),
+                Some(other) => value.extend(&['\\', other]),
+                None => value.push('\\'),
+            },
+            (None, '\\') => value.extend(chars.next()),
+            _ => value.push(c),
+        }
+    }
+
+    value
+}
+
+/// Detect if ChromeOS is installed at the given path, from the `CHROMEOS_RELEASE_*` keys
+/// of its `/etc/lsb-release`.
+///
+/// The description is the release name followed by its version description, such as
+/// `Chrome OS 15474.84.0 (Official Build) stable-channel`.
+pub fn detect_chromeos(base: &Path) -> Option<OS> {
+    let path = base.join("etc/lsb-release");
+    let file = open(&path).ok()?;
+    trace!("found {:?}", path);
+    let release = parse_key_values(BufReader::new(file));
+    let name = release.get("CHROMEOS_RELEASE_NAME")?;
+    let version = release
+        .get("CHROMEOS_RELEASE_DESCRIPTION")
+        .or_else(|| release.get("CHROMEOS_RELEASE_VERSION"));
+
+    Some(OS::ChromeOs(match version {
+        Some(version) => format!("{} {}", name, version),
+        None => name.clone(),
+    }))
+}
+
+/// Whether the install at the given path is ChromeOS, which also ships an os-release, but
+/// is left to `detect_chromeos` rather than being detected as Linux.
+fn is_chromeos(base: &Path) -> bool {
+    let chromeos = detect_chromeos(base).is_some();
+    if chromeos {
+        trace!("leaving {:?} to detect_chromeos", base);
+    }
+
+    chromeos
+}
+
+/// Detect if an Android system image is at the given path, from the `build.prop` found
+/// in its `system` directory or at the root of the partition.
+///
+/// The version is the release from `ro.build.version.release`, followed by the device
+/// model in `ro.product.model` when present, such as `Android 9 (Android-x86)`.
+pub fn detect_android(base: &Path) -> Option<OS> {
+    let (path, file) = ["system/build.prop", "build.prop"].iter().find_map(|path| {
+        let path = base.join(path);
+        open(&path).ok().map(|file| (path, file))
+    })?;
+    trace!("found {:?}", path);
+
+    let props = parse_key_values(BufReader::new(file));
+    let release = props.get("ro.build.version.release")?;
+    Some(OS::Android(match props.get("ro.product.model") {
+        Some(model) => format!("Android {} ({})", release, model),
+        None => format!("Android {}", release),
+    }))
+}
+
+/// Detect if a BSD is installed at the given path.
+///
+/// FreeBSD is recognized by its `/bin/freebsd-version` script, while OpenBSD and NetBSD are
+/// recognized by their kernels at `/bsd` and `/netbsd`, each alongside an `/etc/rc.conf`.
+/// Detection runs before that of Linux, as FreeBSD may also carry an `/etc/os-release`.
+pub fn detect_bsd(base: &Path) -> Option<OS> {
+    let rc_conf = base.join("etc/rc.conf");
+    if !rc_conf.exists() {
+        return None;
+    }
+
+    trace!("found {:?}", rc_conf);
+
+    let freebsd_version = base.join("bin/freebsd-version");
+    let (flavor, version) = if freebsd_version.exists() {
+        ("FreeBSD", read_freebsd_version(&freebsd_version).or_else(|| os_release_version(base)))
+    } else if base.join("bsd").is_file() {
+        ("OpenBSD", os_release_version(base))
+    } else if base.join("netbsd").is_file() {
+        ("NetBSD", os_release_version(base))
+    } else {
+        return None;
+    };
+
+    Some(OS::Bsd { flavor: flavor.into(), version })
+}
+
+/// Reads the userland version that is embedded in the `freebsd-version` script.
+fn read_freebsd_version(path: &Path) -> Option<String> {
+    let file = open(path).ok()?;
+    parse_key_values(BufReader::new(file)).remove("USERLAND_VERSION")
+}
+
+fn os_release_version(base: &Path) -> Option<String> {
+    let info = OsRelease::new_from(base.join("etc/os-release")).ok()?;
+    Some(info.version_id).filter(|version| !version.is_empty())
+}
+
+/// Detects the bootloader installed on an EFI system partition mounted at the given path.
+///
+/// If the partition holds more than one bootloader, a Linux bootloader takes precedence
+/// over the Windows Boot Manager, as it will be what chainloads Windows.
+pub fn detect_bootloader(efi: &Path) -> Option<Bootloader> {
+    let efi = efi.join("EFI");
+    if efi.join("systemd").is_dir() {
+        Some(Bootloader::SystemdBoot)
+    } else if ["grub", "ubuntu", "debian", "fedora"].iter().any(|dir| efi.join(dir).is_dir()) {
+        Some(Bootloader::Grub)
+    } else if efi.join("Microsoft/Boot/bootmgfw.efi").exists() {
+        Some(Bootloader::WindowsBootManager)
+    } else {
+        None
+    }
+}
+
+/// Detect if Mac OS is installed at the given path.
+///
+/// Older macOS Server installs only carry a `ServerVersion.plist`, in which case the
+/// product name is prefixed with `Server: `.
+///
+/// Since Catalina, the plist lives on a sealed System volume, and user data on a separate
+/// Data volume of the same APFS container. A Data volume is recognized by its `private/var`
+/// and `Users` directories, and reported as `macOS (Data volume)` without a version.
+pub fn detect_macos(base: &Path) -> Option<OS> {
+    let system = base.join("System/Library/CoreServices/SystemVersion.plist");
+    if system.exists() {
+        trace!("found {:?}", system);
+        return read_macos_version(&system);
+    }
+
+    let server = base.join("System/Library/CoreServices/ServerVersion.plist");
+    if server.exists() {
+        trace!("found {:?}", server);
+        let mut os = read_macos_version(&server);
+        if let Some(OS::MacOs { ref mut product, .. }) = os {
+            product.insert_str(0, "Server: ");
+        }
+
+        return os;
+    }
+
+    (base.join("private/var").is_dir() && base.join("Users").is_dir()).then(|| OS::MacOs {
+        product: "macOS (Data volume)".into(),
+        version: String::new(),
+        build: None,
+    })
+}
+
+/// Detect if Windows is installed at the given path.
+///
+/// The product name, version, build, and edition are read from the `SOFTWARE` registry
+/// hive when possible, such as `Windows 10 Pro 22H2 (19045)`. Otherwise, the product name
+/// will simply be `Windows`. The architecture is that of the kernel image, or else a 64-bit
+/// install is recognized by its `SysWOW64` directory.
+///
+/// WSL distributions are found in the app packages of each user, by their `ext4.vhdx`
+/// disk image, or the unpacked `rootfs` of WSL 1.
+///
+/// A system reserved partition, which holds the boot files of a Windows install on
+/// another partition, is reported as `Windows Boot Manager`.
+pub fn detect_windows(base: &Path) -> Option<OS> {
+    // NTFS preserves case without enforcing it, so the directory may be `windows` or
+    // `WINDOWS` when mounted case-sensitively.
+    let kernel = join_case_insensitive(base, "Windows/System32/ntoskrnl.exe");
+    if kernel.exists() {
+        trace!("found {:?}", kernel);
+        // ARM64 installs have a `SysWOW64` too, so it's only relied on for an unreadable kernel.
+        let wow64 = || join_case_insensitive(base, "Windows/SysWOW64").is_dir();
+        let arch =
+            kernel_arch(&kernel).unwrap_or_else(|| if wow64() { Arch::X86_64 } else { Arch::X86 });
+        let version = windows_version(base)
+            .or_else(|| kernel_version(&kernel))
+            .unwrap_or_else(|| WindowsVersion::named("Windows"));
+        let mut os = version.into_os(Some(arch));
+        if let OS::Windows { ref mut wsl, ref mut hibernated, ref mut pagefile, .. } = os {
+            *wsl = find_wsl_distros(base);
+            *hibernated = is_hibernated(base);
+            *pagefile = join_case_insensitive(base, "pagefile.sys").is_file();
+        }
+
+        return Some(os);
+    }
+
+    if !(base.join("bootmgr").exists() && base.join("Boot/BCD").exists()) {
+        return None;
+    }
+
+    let mut os = WindowsVersion::named("Windows Boot Manager").into_os(None);
+    if let OS::Windows { ref mut boot_manager, .. } = os {
+        *boot_manager = true;
+    }
+
+    Some(os)
+}
+
+/// The version details of a Windows install, as read from its registry.
+#[derive(Default)]
+struct WindowsVersion {
+    product_name: String,
+    version: Option<String>,
+    build: Option<u32>,
+    edition: Option<String>,
+}
+
+impl WindowsVersion {
+    /// A version with nothing known beyond its product name.
+    fn named(product_name: &str) -> Self {
+        WindowsVersion { product_name: product_name.into(), ..Default::default() }
+    }
+
+    fn into_os(self, arch: Option<Arch>) -> OS {
+        OS::Windows {
+            product_name: self.product_name,
+            version: self.version,
+            build: self.build,
+            edition: self.edition,
+            arch,
+            wsl: Vec::new(),
+            hibernated: false,
+            pagefile: false,
+            boot_manager: false,
+        }
+    }
+}
+
+/// Checks for a hibernation file with contents, as Windows truncates it on a full shutdown
+/// when hibernation is left enabled.
+fn is_hibernated(base: &Path) -> bool {
+    let hiberfil = join_case_insensitive(base, "hiberfil.sys");
+    fs::metadata(&hiberfil).is_ok_and(|metadata| metadata.is_file() && metadata.len() > 0)
+}
+
+/// Finds WSL distributions in `Users/*/AppData/Local/Packages/*/LocalState`, without
+/// descending any further into the user profiles.
+fn find_wsl_distros(base: &Path) -> Vec<String> {
+    let mut distros = Vec::new();
+    for user in read_dir_names(&base.join("Users")) {
+        let packages = base.join("Users").join(user).join("AppData/Local/Packages");
+        for package in read_dir_names(&packages) {
+            let state = packages.join(&package).join("LocalState");
+            let rootfs = state.join("rootfs/etc/os-release");
+            if rootfs.is_file() {
+                trace!("found {:?}", rootfs);
+                let info = OsRelease::new_from(&rootfs).unwrap_or_default();
+                let name = if info.pretty_name.is_empty() { info.name } else { info.pretty_name };
+                distros.push(if name.is_empty() { wsl_package_name(&package) } else { name });
+            } else if state.join("ext4.vhdx").is_file() {
+                trace!("found {:?}", state.join("ext4.vhdx"));
+                distros.push(wsl_package_name(&package));
+            }
+        }
+    }
+
+    distros.sort();
+    distros
+}
+
+/// The name of an app package, such as `Ubuntu22.04LTS` for
+/// `CanonicalGroupLimited.Ubuntu22.04LTS_79rhkp1fndgsc`, without its publisher.
+fn wsl_package_name(package: &str) -> String {
+    let name = package.split('_').next().unwrap_or(package);
+    name.split_once('.').map_or(name, |(_, name)| name).into()
+}
+
+fn windows_version(base: &Path) -> Option<WindowsVersion> {
+    let path = join_case_insensitive(base, "Windows/System32/config/SOFTWARE");
+    let file = open(&path).ok()?;
+    match read_windows_version(file) {
+        Ok(version) => version,
+        Err(why) => {
+            warn!("unable to read the Windows version from {:?}: {}", path, why);
+            None
+        }
+    }
+}
+
+/// Reads the build of Windows from the version resource of its kernel, for when the
+/// registry can't be read. Only the build is known, as the product name and feature
+/// update are only recorded in the registry.
+fn kernel_version(kernel: &Path) -> Option<WindowsVersion> {
+    let file = open(kernel).ok()?;
+    match pe::file_version(BufReader::new(file)) {
+        Ok(version) => version.map(|version| WindowsVersion {
+            build: Some(u32::from(version.build)),
+            ..WindowsVersion::named("Windows")
+        }),
+        Err(why) => {
+            warn!("unable to read the version of {:?}: {}", kernel, why);
+            None
+        }
+    }
+}
+
+/// The build of the first release of Windows 11, whose `ProductName` in the registry was
+/// left as that of Windows 10.
+const FIRST_WINDOWS_11_BUILD: u32 = 22000;
+
+/// Decodes the architecture of Windows from the machine type of its kernel image.
+fn kernel_arch(kernel: &Path) -> Option<Arch> {
+    const IMAGE_FILE_MACHINE_I386: u16 = 0x14C;
+    const IMAGE_FILE_MACHINE_ARMNT: u16 = 0x1C4;
+    const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+    const IMAGE_FILE_MACHINE_ARM64: u16 = 0xAA64;
+
+    let file = open(kernel).ok()?;
+    match pe::machine(BufReader::new(file)) {
+        Ok(IMAGE_FILE_MACHINE_I386) => Some(Arch::X86),
+        Ok(IMAGE_FILE_MACHINE_ARMNT) => Some(Arch::Arm),
+        Ok(IMAGE_FILE_MACHINE_AMD64) => Some(Arch::X86_64),
+        Ok(IMAGE_FILE_MACHINE_ARM64) => Some(Arch::Aarch64),
+        Ok(machine) => {
+            warn!("unknown machine type {:#x} of {:?}", machine, kernel);
+            None
+        }
+        Err(why) => {
+            debug!("unable to read the machine type of {:?}: {}", kernel, why);
+            None
+        }
+    }
+}
+
+fn read_windows_version(file: File) -> io::Result<Option<WindowsVersion>> {
+    let mut hive = Hive::new(BufReader::new(file))?;
+    let key = match hive.key(&["Microsoft", "Windows NT", "CurrentVersion"])? {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+
+    let product_name = match hive.value(key, "ProductName")? {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+
+    let build = hive.value(key, "CurrentBuild")?.and_then(|build| build.parse().ok());
+    let product_name = match product_name.strip_prefix("Windows 10") {
+        Some(edition) if build.is_some_and(|build| build >= FIRST_WINDOWS_11_BUILD) => {
+            format!("Windows 11{}", edition)
+        }
+        _ => product_name,
+    };
+
+    Ok(Some(WindowsVersion {
+        product_name,
+        version: hive.value(key, "DisplayVersion")?,
+        build,
+        edition: hive.value(key, "EditionID")?,
+    }))
+}
+
+/// Reads the machine ID, accepting only the 32 hexadecimal characters which the
+/// machine-id format requires.
+fn read_machine_id(base: &Path) -> Option<String> {
+    ["etc/machine-id", "var/lib/dbus/machine-id"].iter().find_map(|path| {
+        let id = fs::read_to_string(base.join(path)).ok()?;
+        let id = id.trim();
+        if id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            Some(id.to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+fn read_hostname(base: &Path) -> Option<String> {
+    if let Ok(hostname) = fs::read_to_string(base.join("etc/hostname")) {
+        let hostname =
+            hostname.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#'));
+        return hostname.map(String::from);
+    }
+
+    // Debian-based installs map their hostname to 127.0.1.1.
+    let hosts = fs::read_to_string(base.join("etc/hosts")).ok()?;
+    hosts.lines().map(|line| line.split('#').next().unwrap_or("").split_whitespace()).find_map(
+        |mut fields| match fields.next() {
+            Some("127.0.1.1") => fields.next().map(String::from),
+            _ => None,
+        },
+    )
+}
+
+/// Where each network configuration tool keeps its configuration, by its directory and a
+/// pattern of file names, in which `*` matches anything.
+const NETWORK_CONFIG: &[(&str, &str)] = &[
+    // ifupdown, on Debian.
+    ("etc/network", "interfaces"),
+    ("etc/network/interfaces.d", "*"),
+    // network-scripts, on RHEL and its derivatives.
+    ("etc/sysconfig/network-scripts", "ifcfg-*"),
+    // netplan, on Ubuntu.
+    ("etc/netplan", "*.yaml"),
+    ("etc/NetworkManager/system-connections", "*"),
+    ("etc/systemd/network", "*.network"),
+];
+
+/// Lists the configuration files of `NETWORK_CONFIG` that exist, relative to `base`.
+fn find_network_config(base: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for &(dir, pattern) in NETWORK_CONFIG {
+        let mut names: Vec<String> = read_dir_names(&base.join(dir))
+            .filter(|name| matches_pattern(name, pattern))
+            .filter(|name| base.join(dir).join(name).is_file())
+            .collect();
+        names.sort();
+        found.extend(names.into_iter().map(|name| Path::new(dir).join(name)));
+    }
+
+    found
+}
+
+/// Whether a file name matches a pattern with at most one `*`, which matches anything.
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        }
+        None => name == pattern,
+    }
+}
+
+/// Files which are written to while an install runs, or as it boots and shuts down.
+const ACTIVITY_FILES: &[&str] = &[
+    "etc/os-release",
+    "var/log/wtmp",
+    "var/log/lastlog",
+    "var/log/journal",
+    "var/lib/systemd/random-seed",
+];
+
+/// Files in each home directory which are written to as its user works.
+const USER_ACTIVITY_FILES: &[&str] =
+    &[".bash_history", ".zsh_history", ".local/share/recently-used.xbel"];
+
+/// The latest modification time among the `ACTIVITY_FILES` and `USER_ACTIVITY_FILES`
+/// which exist.
+fn last_seen(base: &Path) -> Option<SystemTime> {
+    let home = base.join("home");
+    let homes: Vec<PathBuf> = read_dir_names(&home).map(|user| home.join(user)).collect();
+    let user_files =
+        homes.iter().flat_map(|home| USER_ACTIVITY_FILES.iter().map(move |file| home.join(file)));
+
+    ACTIVITY_FILES
+        .iter()
+        .map(|file| base.join(file))
+        .chain(user_files)
+        .filter_map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .max()
+}
+
+/// Reads the timezone that `/etc/localtime` links to within the zoneinfo database, falling
+/// back to the name in `/etc/timezone`, as used by Debian.
+fn read_timezone(base: &Path) -> Option<String> {
+    if let Ok(target) = fs::read_link(base.join("etc/localtime")) {
+        let target = target.to_string_lossy();
+        if let Some((_, zone)) = target.split_once("zoneinfo/") {
+            // The `posix` and `right` trees hold copies of each zone, with and without leap
+            // seconds.
+            let leap = zone.strip_prefix("posix/").or_else(|| zone.strip_prefix("right/"));
+            return Some(leap.unwrap_or(zone).to_owned());
+        }
+    }
+
+    let timezone = fs::read_to_string(base.join("etc/timezone")).ok()?;
+    timezone
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+}
+
+/// Reads the `LANG` from the first of the files that may set the default locale. Systemd
+/// installs use `/etc/locale.conf`, while Debian's use `/etc/default/locale`.
+fn read_locale(base: &Path) -> Option<String> {
+    const LOCALE_FILES: &[&str] = &["etc/locale.conf", "etc/default/locale", "etc/environment"];
+    let contents = LOCALE_FILES.iter().find_map(|path| fs::read_to_string(base.join(path)).ok())?;
+    contents
+        .lines()
+        .map(|line| line.trim().trim_start_matches("export "))
+        .find_map(|line| line.strip_prefix("LANG="))
+        .map(unquote_os_release_value)
+        .filter(|lang| !lang.is_empty())
+}
+
+/// Collects kernel versions from `vmlinuz-*` images and kernel module directories.
+fn find_kernels(base: &Path) -> Vec<String> {
+    let mut kernels = Vec::new();
+
+    let images = read_dir_names(&base.join("boot"));
+    kernels.extend(images.filter_map(|name| name.strip_prefix("vmlinuz-").map(String::from)));
+
+    for modules in &["lib/modules", "usr/lib/modules"] {
+        kernels.extend(read_dir_names(&base.join(modules)));
+    }
+
+    kernels.sort_by(|a, b| compare_versions(a, b));
+    kernels.dedup();
+    kernels
+}
+
+/// Names of the entries in a directory, or nothing if it can't be read.
+fn read_dir_names(path: &Path) -> impl Iterator<Item = String> {
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+}
+
+/// Joins a relative path to `base`, matching each of its components to an existing entry
+/// regardless of case. Components without a match are joined as they are.
+fn join_case_insensitive(base: &Path, path: &str) -> PathBuf {
+    let mut joined = base.to_path_buf();
+    for component in path.split('/') {
+        let name = if joined.join(component).exists() {
+            component.to_owned()
+        } else {
+            read_dir_names(&joined)
+                .find(|name| name.eq_ignore_ascii_case(component))
+                .unwrap_or_else(|| component.to_owned())
+        };
+
+        joined.push(name);
+    }
+
+    joined
+}
+
+/// Compares version strings segment by segment, with numeric segments compared by value.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn segments(version: &str) -> impl Iterator<Item = &str> {
+        version.split(|c: char| !c.is_ascii_alphanumeric()).filter(|s| !s.is_empty())
+    }
+
+    let mut a = segments(a);
+    let mut b = segments(b);
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match (x.parse::<u64>(), y.parse::<u64>()) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                _ => x.cmp(y),
+            },
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// The entries of a fstab which could be resolved to a partition.
+#[derive(Debug, Default)]
+struct Fstab {
+    entries: Vec<FstabEntry>,
+}
+
+#[derive(Debug)]
+struct FstabEntry {
+    source: PartitionID,
+    target: PathBuf,
+    fs: String,
+    options: String,
+    /// Set when the entry's `/dev/mapper` source was resolved through the crypttab.
+    encrypted: bool,
+}
+
+/// Directories of `/dev` which hold devices other than LVM volume groups.
+const NON_LVM_DEV_DIRS: &[&str] = &["block", "bus", "char", "disk", "fd", "md", "net", "shm"];
+
+/// Whether a device-mapper name is known not to be that of a logical volume, despite any
+/// hyphen within it: LUKS volumes opened without a crypttab entry are named `luks-<uuid>`,
+/// other dm-crypt volumes customarily have `crypt` in their name, and multipath devices
+/// and their partitions are named `mpatha`, `mpatha-part1` and so on.
+fn is_non_lvm_mapper(name: &str) -> bool {
+    name.starts_with("luks-") || name.starts_with("mpath") || name.contains("crypt")
+}
+
+/// The `vg/lv` name of a logical volume, from its `/dev/mapper/vg-lv` or `/dev/vg/lv` path.
+///
+/// Hyphens within device-mapper names are escaped by doubling them, so
+/// `/dev/mapper/my--vg-root` is the `root` volume of `my-vg`. Mapper names of encrypted
+/// and multipath devices aren't taken as volumes.
+fn lvm_volume(source: &PartitionID) -> Option<String> {
+    if source.variant != PartitionSource::Path {
+        return None;
+    }
+
+    let path = source.id.strip_prefix("/dev/")?;
+    if let Some(name) = path.strip_prefix("mapper/") {
+        if is_non_lvm_mapper(name) {
+            return None;
+        }
+
+        let bytes = name.as_bytes();
+        let split = (1..bytes.len()).find(|&i| {
+            bytes[i] == b'-' && bytes[i - 1] != b'-' && bytes.get(i + 1) != Some(&b'-')
+        })?;
+        let (vg, lv) = (&name[..split], &name[split + 1..]);
+        return Some(format!("{}/{}", vg.replace("--", "-"), lv.replace("--", "-")));
+    }
+
+    match path.split('/').collect::<Vec<_>>().as_slice() {
+        [vg, lv] if !NON_LVM_DEV_DIRS.contains(vg) && !lv.is_empty() => {
+            Some(format!("{}/{}", vg, lv))
+        }
+        _ => None,
+    }
+}
+
+impl Fstab {
+    /// Sources of the swap entries, which have no real mount target.
+    fn swap_entries(&self) -> impl Iterator<Item = &PartitionID> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.fs == "swap")
+            .filter(|entry| entry.target == Path::new("swap") || entry.target == Path::new("none"))
+            .map(|entry| &entry.source)
+    }
+
+    /// Sources of the swap partitions, which are those of the swap entries that aren't
+    /// swap files.
+    fn swap(&self) -> Vec<PartitionID> {
+        self.swap_entries().filter(|source| !is_swapfile(source)).cloned().collect()
+    }
+
+    /// The path of the first swap entry which is a file, rather than a device.
+    fn swapfile(&self) -> Option<String> {
+        self.swap_entries().find(|source| is_swapfile(source)).map(|source| source.id.clone())
+    }
+
+    /// Finds the entry mounted at the given target.
+    fn find(&self, target: &str) -> Option<&FstabEntry> {
+        self.entries.iter().find(|entry| entry.target == Path::new(target))
+    }
+
+    /// Describes where the given target is mounted from, distinguishing subvolumes of
+    /// the root filesystem from separate partitions.
+    fn mount_source(&self, target: &str) -> Option<MountSource> {
+        let entry = self.find(target)?;
+        if let Some(subvolume) = entry.subvolume() {
+            if self.find("/").is_some_and(|root| root.source == entry.source) {
+                return Some(MountSource::Subvolume(subvolume.into()));
+            }
+        }
+
+        if let Some(volume) = lvm_volume(&entry.source) {
+            return Some(MountSource::LvmVolume(volume));
+        }
+
+        Some(MountSource::Partition { id: entry.source.clone(), encrypted: entry.encrypted })
+    }
+
+    /// Replaces the `/dev/mapper` sources of entries that are encrypted volumes with the
+    /// partitions backing them, and marks them as encrypted.
+    fn resolve_encrypted(&mut self, crypttab: &BTreeMap<String, PartitionID>) {
+        for entry in &mut self.entries {
+            if entry.source.variant != PartitionSource::Path {
+                continue;
+            }
+
+            let name = entry.source.id.strip_prefix("/dev/mapper/");
+            let backing = name.and_then(|name| crypttab.get(name));
+            if let Some(backing) = backing {
+                entry.source = backing.clone();
+                entry.encrypted = true;
+            }
+        }
+    }
+}
+
+impl FstabEntry {
+    /// The btrfs subvolume named by the `subvol=` mount option, without a leading `/`.
+    fn subvolume(&self) -> Option<&str> {
+        self.options
+            .split(',')
+            .find(|option| option.starts_with("subvol="))
+            .map(|option| option["subvol=".len()..].trim_start_matches('/'))
+    }
+}
+
+fn find_linux_parts(base: &Path) -> Fstab {
+    let mut fstab = match open(base.join("etc/fstab")) {
+        Ok(fstab) => parse_fstab(BufReader::new(fstab)),
+        Err(_) => Fstab::default(),
+    };
+
+    if let Ok(crypttab) = open(base.join("etc/crypttab")) {
+        fstab.resolve_encrypted(&parse_crypttab(BufReader::new(crypttab)));
+    }
+
+    fstab
+}
+
+/// Maps the names of the volumes in a crypttab to the partitions that back them.
+fn parse_crypttab<R: BufRead>(crypttab: R) -> BTreeMap<String, PartitionID> {
+    let mut volumes = BTreeMap::new();
+    for entry in crypttab.lines().map_while(Result::ok) {
+        let entry = entry.trim();
+        if entry.starts_with('#') || entry.is_empty() {
+            continue;
+        }
+
+        let mut fields = entry.split_whitespace();
+        if let (Some(name), Some(device)) = (fields.next(), fields.next()) {
+            if let Some(device) = parse_fstab_source(device) {
+                volumes.insert(name.to_owned(), device);
+            }
+        }
+    }
+
+    volumes
+}
+
+/// Network and pseudo filesystems, whose fstab entries never refer to a local partition.
+const NON_LOCAL_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smbfs",
+    "sshfs",
+    "fuse.sshfs",
+    "9p",
+    "tmpfs",
+    "ramfs",
+    "overlay",
+    "proc",
+    "sysfs",
+    "devpts",
+    "devtmpfs",
+    "cgroup",
+    "cgroup2",
+];
+
+/// Parses an fstab as `mount` does: fields are separated by any run of spaces and tabs,
+/// and blank lines and those starting with `#` are skipped. A field starting with `#` is
+/// also taken to start a comment, as fields can't hold whitespace to separate it with.
+fn parse_fstab<R: BufRead>(fstab: R) -> Fstab {
+    let mut parts = Fstab::default();
+
+    for entry in fstab.lines().map_while(Result::ok) {
+        // Trimming also strips the `\r` of CRLF line endings, as in a hand-edited fstab.
+        let entry = entry.trim();
+        if entry.starts_with('#') || entry.is_empty() {
+            continue;
+        }
+
+        let mut fields = entry.split_whitespace().take_while(|field| !field.starts_with('#'));
+        let source = fields.next().map(unescape_fstab_field);
+        let target = fields.next().map(unescape_fstab_field);
+        let fs = fields.next().unwrap_or("auto");
+        let options = fields.next().unwrap_or("defaults");
+
+        if NON_LOCAL_FILESYSTEMS.contains(&fs) {
+            trace!("skipping {} mount of {:?}", fs, target);
+            continue;
+        }
+
+        if let Some(target) = target {
+            if let Some(source) = source.as_deref().and_then(parse_fstab_source) {
+                parts.entries.push(FstabEntry {
+                    source,
+                    target: PathBuf::from(target),
+                    fs: fs.into(),
+                    options: options.into(),
+                    encrypted: false,
+                });
+            }
+        }
+    }
+
+    parts
+}
+
+/// Decodes the octal escapes of an fstab field, such as `\040` for a space in a label or
+/// mount point.
+fn unescape_fstab_field(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(start) = rest.find('\\') {
+        unescaped.push_str(&rest[..start]);
+        let octal = rest.get(start + 1..start + 4);
+        let octal = octal.filter(|octal| octal.bytes().all(|digit| (b'0'..=b'7').contains(&digit)));
+        match octal.and_then(|octal| u8::from_str_radix(octal, 8).ok()) {
+            Some(byte) if byte.is_ascii() => {
+                unescaped.push(char::from(byte));
+                rest = &rest[start + 4..];
+            }
+            _ => {
+                unescaped.push('\\');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Parses the source of a fstab entry, which may be a `UUID=`, `PARTUUID=`, `LABEL=`, or
+/// `PARTLABEL=` key, or a device path. Paths within `/dev/disk/by-*` are converted into the
+/// identifier kind that they represent.
+fn parse_fstab_source(source: &str) -> Option<PartitionID> {
+    if source.starts_with("/dev/disk/by-") {
+        if let Ok(id) = PartitionID::from_disk_by_path(source) {
+            return Some(id);
+        }
+    }
+
+    source.parse::<PartitionID>().ok()
+}
+
+/// Whether the source of a swap entry is a file, such as `/swapfile`, rather than a device.
+fn is_swapfile(source: &PartitionID) -> bool {
+    source.variant == PartitionSource::Path && !source.id.starts_with("/dev/")
+}
+
+/// Parses `KEY=value` lines, skipping comments and unquoting values with
+/// `unquote_os_release_value`.
+fn parse_key_values<R: BufRead>(file: R) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    for line in file.lines().map_while(Result::ok) {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim().to_owned(), unquote_os_release_value(value));
+        }
+    }
+
+    values
+}
+
+fn read_macos_version(path: &Path) -> Option<OS> {
+    let file = open(path).ok()?;
+    let version = parse_plist(BufReader::new(file));
+    if version.is_none() {
+        warn!("unable to parse the macOS version from {:?}", path);
+    }
+
+    version
+}
+
+fn parse_plist<R: BufRead>(file: R) -> Option<OS> {
+    // The plist is an XML file, but we don't need complex XML parsing for this.
+    // Lines are trimmed so that no stray `\r` is left by CRLF line endings.
+    let mut plist = String::new();
+    for line in file.lines().map_while(Result::ok) {
+        plist.push_str(line.trim());
+        plist.push('\n');
+    }
+
+    Some(OS::MacOs {
+        product: plist_string(&plist, "ProductName")?.into(),
+        version: plist_string(&plist, "ProductUserVisibleVersion")?.into(),
+        build: plist_string(&plist, "ProductBuildVersion").map(String::from),
+    })
+}
+
+/// Fetches the `<string>` value which immediately follows the given `<key>` in a plist.
+fn plist_string<'a>(plist: &'a str, key: &str) -> Option<&'a str> {
+    const START: &str = "<string>";
+    const END: &str = "</string>";
+
+    let key = format!("<key>{}</key>", key);
+    let value = plist[plist.find(&key)? + key.len()..].trim_start();
+    if !value.starts_with(START) {
+        return None;
+    }
+
+    let value = &value[START.len()..];
+    value.find(END).map(|end| value[..end].trim())
+}
+
+/// Describes the filesystem type for error reporting.
+fn filesystem_name(fs: &FilesystemType) -> String {
+    match *fs {
+        FilesystemType::Auto(_) => "auto".into(),
+        FilesystemType::Manual(fs) => fs.into(),
+        FilesystemType::Set(set) => set.join(","),
+    }
+}
+
+fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
+    File::open(&path).map_err(|why| {
+        io::Error::other(format!("unable to open file at {:?}: {}", path.as_ref(), why))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    extern crate serde_json;
+
+    use super::*;
+    use std::io::Cursor;
+
+    const POP_OS_RELEASE: &str = r#"NAME="Pop!_OS"
+VERSION="18.04 LTS"
+ID=ubuntu
+ID_LIKE=debian
+PRETTY_NAME="Pop!_OS 18.04 LTS"
+VERSION_ID="18.04"
+VERSION_CODENAME=bionic"#;
+
+    const POP_2204_OS_RELEASE: &str = r#"NAME="Pop!_OS"
+VERSION="22.04 LTS"
+ID=pop
+ID_LIKE="ubuntu debian"
+PRETTY_NAME="Pop!_OS 22.04 LTS"
+VERSION_ID="22.04"
+VERSION_CODENAME=jammy"#;
+
+    const MAC_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "Apple Stuff">
+<plist version="1.0">
+<dict>
+    <key>ProductBuildVersion</key>
+    <string>10C540</string>
+    <key>ProductName</key>
+    <string>Mac OS X</string>
+    <key>ProductUserVisibleVersion</key>
+    <string>10.6.2</string>
+    <key>ProductVersion</key>
+    <string>10.6.2</string>
+</dict>
+</plist>"#;
+
+    /// The macOS described by `MAC_PLIST`.
+    fn snow_leopard() -> OS {
+        OS::MacOs {
+            product: "Mac OS X".into(),
+            version: "10.6.2".into(),
+            build: Some("10C540".into()),
+        }
+    }
+
+    #[test]
+    fn mac_plist_parsing() {
+        let os = parse_plist(Cursor::new(MAC_PLIST));
+        match os {
+            Some(OS::MacOs { ref build, .. }) => assert_eq!(build.as_deref(), Some("10C540")),
+            ref other => panic!("expected macOS, found {:?}", other),
+        }
+
+        assert_eq!(os, Some(snow_leopard()));
+    }
+
+    /// Creates a temporary directory tree populated with the given files.
+    fn fixture(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new("os-detect-test").unwrap();
+        for &(path, contents) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        dir
+    }
+
+    /// Detects the Linux install in a fixture of Pop!_OS with the given files.
+    fn detect_pop_os(files: &[(&str, &str)]) -> LinuxInstall {
+        detect_pop_os_with(files, |_| ())
+    }
+
+    /// As `detect_pop_os`, once `prepare` has added what `fixture` can't, such as links.
+    fn detect_pop_os_with<F: FnOnce(&Path)>(files: &[(&str, &str)], prepare: F) -> LinuxInstall {
+        let mut files = files.to_vec();
+        files.push(("etc/os-release", POP_OS_RELEASE));
+        let dir = fixture(&files);
+        prepare(dir.path());
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(linux)) => linux,
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    /// Detects the Windows install in a fixture of only a kernel and the given files.
+    fn detect_bare_windows(files: &[(&str, &str)]) -> Option<OS> {
+        let mut files = files.to_vec();
+        files.push(("Windows/System32/ntoskrnl.exe", ""));
+        detect_windows(fixture(&files).path())
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let plist = MAC_PLIST.replace('\n', "\r\n");
+        assert_eq!(parse_plist(Cursor::new(plist)), Some(snow_leopard()));
+
+        let plist = "<key>ProductName</key>\r<string>Mac OS X\r</string>\r\n\
+            <key>ProductUserVisibleVersion</key>\r\n<string>10.6.2</string>\r\n";
+        let os = parse_plist(Cursor::new(plist)).map(|os| os.to_string());
+        assert_eq!(os.as_deref(), Some("Mac OS X (10.6.2)"));
+
+        let fstab = parse_fstab(Cursor::new(FSTAB.replace('\n', "\r\n")));
+        assert_eq!(fstab.entries.len(), 6);
+        assert!(fstab.entries.iter().all(|entry| !entry.options.contains('\r')));
+        assert_eq!(fstab.find("/srv").map(|entry| entry.source.id.as_str()), Some("/dev/sda5"));
+
+        let dir = fixture(&[
+            ("etc/os-release", &POP_OS_RELEASE.replace('\n', "\r\n")),
+            ("etc/fstab", &FSTAB.replace('\n', "\r\n")),
+        ]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, targets, .. })) => {
+                assert_eq!(info.pretty_name, "Pop!_OS 18.04 LTS");
+                assert_eq!(info.version_codename, "bionic");
+                assert_eq!(targets.last(), Some(&PathBuf::from("/srv")));
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn mac_plist_parsing_single_line() {
+        let plist = "<dict><key>ProductName</key><string>Mac OS X</string>\
+            <key>ProductUserVisibleVersion</key><string>10.6.2</string></dict>";
+        let os = parse_plist(Cursor::new(plist)).map(|os| os.to_string());
+        assert_eq!(os.as_deref(), Some("Mac OS X (10.6.2)"));
+    }
+
+    #[test]
+    fn mac_plist_parsing_short_values() {
+        let plist = "<dict>\n\t<key>ProductName</key>\n\t<string>OS</string>\n\
+            \t<key>ProductUserVisibleVersion</key>\n\t\t<string>11</string>\n</dict>";
+        let os = parse_plist(Cursor::new(plist)).map(|os| os.to_string());
+        assert_eq!(os.as_deref(), Some("OS (11)"));
+    }
+
+    #[test]
+    fn mac_plist_parsing_missing_value() {
+        let plist = "<key>ProductName</key>\n<string>Mac OS X</string>\n\
+            <key>ProductUserVisibleVersion</key>\n<integer>10</integer>";
+        assert_eq!(parse_plist(Cursor::new(plist)), None);
+    }
+
+    #[test]
+    fn probe_mounts_read_only_first() {
+        assert!(PROBE_MOUNT_FLAGS[0].contains(MountFlags::RDONLY));
+        assert!(!PROBE_MOUNT_FLAGS.last().unwrap().contains(MountFlags::RDONLY));
+    }
+
+    /// "Mounts" a device by copying a fixture tree to the target, recording the mounts.
+    #[derive(Debug, Default)]
+    struct FixtureMounter {
+        files: Vec<(String, String)>,
+        mounts: std::sync::Mutex<Vec<(PathBuf, String, MountFlags)>>,
+        refuse_read_only: bool,
+        /// Filesystems that the "kernel" has no driver for.
+        unsupported: Vec<&'static str>,
+    }
+
+    impl FixtureMounter {
+        fn new(files: &[(&str, &str)]) -> Self {
+            let files =
+                files.iter().map(|&(path, contents)| (path.into(), contents.into())).collect();
+            FixtureMounter { files, ..Default::default() }
+        }
+    }
+
+    impl Mounter for Arc<FixtureMounter> {
+        fn mount(
+            &self,
+            device: &Path,
+            target: &Path,
+            fs: &FilesystemType,
+            flags: MountFlags,
+            _data: Option<&str>,
+        ) -> io::Result<Box<dyn Unmount>> {
+            self.mounts.lock().unwrap().push((device.to_path_buf(), filesystem_name(fs), flags));
+            if self.refuse_read_only && flags.contains(MountFlags::RDONLY) {
+                return Err(io::Error::from_raw_os_error(30));
+            }
+
+            if self.unsupported.contains(&filesystem_name(fs).as_str()) {
+                return Err(io::Error::from_raw_os_error(19));
+            }
+
+            for (path, contents) in &self.files {
+                let path = target.join(path);
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::write(path, contents)?;
+            }
+
+            Ok(Box::new(FixtureMount))
+        }
+    }
+
+    struct FixtureMount;
+
+    impl Unmount for FixtureMount {
+        fn unmount(&self, _flags: UnmountFlags) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn image_partition_offset() {
+        let args = |offset: u64, size: Option<u64>, read_only: bool| {
+            let command = losetup_attach(Path::new("disk.img"), offset, size, read_only);
+            command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect::<Vec<_>>()
+        };
+
+        // A partition starting at sector 2048 of a disk with 512 byte sectors.
+        assert_eq!(
+            args(2048 * 512, None, true),
+            ["--find", "--show", "--offset", "1048576", "--read-only", "disk.img"]
+        );
+        assert_eq!(args(0, None, false), ["--find", "--show", "--offset", "0", "disk.img"]);
+        assert_eq!(
+            args(1048576, Some(4096 * 512), false),
+            ["--find", "--show", "--offset", "1048576", "--sizelimit", "2097152", "disk.img"]
+        );
+    }
+
+    #[test]
+    fn loop_mount_unmounted_but_not_detached() {
+        let mount = LoopMount { mount: Box::new(FixtureMount), device: "/dev/missing".into() };
+        assert!(mount.unmount(UnmountFlags::empty()).is_ok());
+    }
+
+    #[test]
+    fn temp_dir_fallback() {
+        // Not even root can create a directory within a file.
+        let dir = fixture(&[("file", "")]);
+        let unwritable = dir.path().join("file/tmp");
+        let writable = dir.path().join("run");
+        fs::create_dir(&writable).unwrap();
+
+        let dirs = [unwritable.clone(), writable.clone()];
+        let tempdir = create_temp_dir(&dirs, "os-detect").unwrap();
+        assert!(tempdir.path().starts_with(&writable));
+
+        match create_temp_dir(&[unwritable.clone(), dir.path().join("missing")], "os-detect") {
+            Err(DetectError::TempDir(why)) => {
+                let why = why.to_string();
+                assert!(why.contains(&format!("{:?}", unwritable)), "{}", why);
+                assert!(why.contains("missing"), "{}", why);
+            }
+            other => panic!("expected a temporary directory error, found {:?}", other.map(|_| ())),
+        }
+
+        let detector = Detector::new().fallback_temp_dir(&writable);
+        let plan = detector.plan(Path::new("/dev/sda3"), "ext4");
+        assert_eq!(plan.temp_dirs, [std::env::temp_dir(), writable]);
+    }
+
+    #[test]
+    fn detection_plan() {
+        let device = Path::new("/dev/sda3");
+        let plan = Detector::new().temp_prefix("installer").plan(device, "ntfs");
+        assert_eq!(plan.device, device);
+        assert_eq!(plan.temp_prefix, "installer");
+        assert_eq!(plan.mount_data, None);
+        assert_eq!(
+            plan.mounts,
+            [
+                ("ntfs3".to_owned(), MountFlags::RDONLY),
+                ("ntfs".to_owned(), MountFlags::RDONLY),
+                ("ntfs3".to_owned(), MountFlags::empty()),
+                ("ntfs".to_owned(), MountFlags::empty()),
+            ]
+        );
+        assert_eq!(plan.detectors, ["BSD", "Linux", "ChromeOS", "Android", "Windows", "macOS"]);
+
+        let plan = Detector::new()
+            .read_only(false)
+            .mount_flags(MountFlags::NOEXEC)
+            .mount_data(Some("noload".into()))
+            .timeout(Some(Duration::from_secs(5)))
+            .plan(device, "ext4");
+        assert_eq!(plan.temp_prefix, "os-detect");
+        assert_eq!(plan.mounts, [("ext4".to_owned(), MountFlags::NOEXEC)]);
+        assert_eq!(plan.mount_data.as_deref(), Some("noload"));
+        assert_eq!(plan.timeout, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn filesystem_driver_aliases() {
+        assert_eq!(filesystem_aliases("ntfs"), ["ntfs3", "ntfs"]);
+        assert_eq!(filesystem_aliases("ntfs3"), ["ntfs3", "ntfs"]);
+        assert_eq!(filesystem_aliases("vfat"), ["vfat", "msdos"]);
+        assert_eq!(filesystem_aliases("ext4"), ["ext4"]);
+    }
+
+    #[test]
+    fn detect_with_filesystem_alias() {
+        let mounter = Arc::new(FixtureMounter {
+            unsupported: vec!["ntfs3"],
+            ..FixtureMounter::new(&[("Windows/System32/ntoskrnl.exe", "")])
+        });
+
+        let detector = Detector::new().mounter(mounter.clone());
+        let os = detector.detect(Path::new("/dev/os-detect-mock"), "ntfs").unwrap();
+        assert!(matches!(os, Some(OS::Windows { .. })));
+
+        let mounts = mounter.mounts.lock().unwrap();
+        let tried: Vec<(&str, bool)> = mounts
+            .iter()
+            .map(|(_, fs, flags)| (fs.as_str(), flags.contains(MountFlags::RDONLY)))
+            .collect();
+        assert_eq!(tried, [("ntfs3", true), ("ntfs", true)]);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn detect_async_with_mock_mounter() {
+        let files = [("etc/os-release", POP_OS_RELEASE), ("etc/fstab", FSTAB)];
+        let mounter = Arc::new(FixtureMounter::new(&files));
+        let detector = Detector::new().mounter(mounter.clone());
+        let future = detector.detect_async(Path::new("/dev/os-detect-mock"), "ext4");
+        match detect_future::tests::block_on(future) {
+            Ok(Some(OS::Linux(LinuxInstall { info, .. }))) => {
+                assert_eq!(info.pretty_name, "Pop!_OS 18.04 LTS");
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        assert_eq!(mounter.mounts.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn filesystem_mismatch() {
+        assert!(same_filesystem("ext4", "ext2"));
+        assert!(same_filesystem("ntfs3", "ntfs"));
+        assert!(same_filesystem("msdos", "vfat"));
+        assert!(!same_filesystem("ext4", "ntfs"));
+        assert!(!same_filesystem("btrfs", "xfs"));
+
+        let mut ntfs = vec![0; 4096];
+        ntfs[3..11].copy_from_slice(b"NTFS    ");
+        let dir = fixture(&[]);
+        let device = dir.path().join("ntfs.img");
+        fs::write(&device, ntfs).unwrap();
+
+        let strict = Detector::new().strict(true);
+        match strict.check_filesystem(&device, &"ext4".into()) {
+            Err(DetectError::FilesystemMismatch { declared, found, .. }) => {
+                assert_eq!((declared.as_str(), found.as_str()), ("ext4", "ntfs"));
+            }
+            other => panic!("expected a filesystem mismatch, found {:?}", other),
+        }
+
+        assert!(strict.check_filesystem(&device, &"ntfs3".into()).is_ok());
+        assert!(strict.check_filesystem(&device, &FilesystemType::Set(&["ext4", "ntfs"])).is_ok());
+        assert!(strict.check_filesystem(&dir.path().join("missing.img"), &"ext4".into()).is_ok());
+        assert!(Detector::new().check_filesystem(&device, &"ext4".into()).is_ok());
+
+        // The superblock of a partition in a disk image is found at its offset.
+        let image = dir.path().join("disk.img");
+        fs::write(&image, [vec![0; 4096], fs::read(&device).unwrap()].concat()).unwrap();
+        let partition = strict.mounter(LoopMounter { offset: 4096, size: None });
+        assert!(partition.check_filesystem(&image, &"ext4".into()).is_err());
+        assert!(partition.check_filesystem(&image, &"ntfs".into()).is_ok());
+    }
+
+    #[test]
+    fn detect_with_mock_mounter() {
+        let files = [("etc/os-release", POP_OS_RELEASE), ("etc/fstab", FSTAB)];
+        let mounter = Arc::new(FixtureMounter::new(&files));
+        let detector = Detector::new().mounter(mounter.clone());
+        match detector.detect(Path::new("/dev/os-detect-mock"), "ext4") {
+            Ok(Some(OS::Linux(LinuxInstall { info, home, .. }))) => {
+                assert_eq!(info.pretty_name, "Pop!_OS 18.04 LTS");
+                assert!(home.is_some());
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let mounts = mounter.mounts.lock().unwrap();
+        let device = PathBuf::from("/dev/os-detect-mock");
+        assert_eq!(*mounts, [(device, "ext4".into(), MountFlags::RDONLY)]);
+    }
+
+    #[test]
+    fn mock_mounter_read_write_fallback() {
+        let mounter = Arc::new(FixtureMounter {
+            refuse_read_only: true,
+            ..FixtureMounter::new(&[("Windows/System32/ntoskrnl.exe", "")])
+        });
+
+        let detector = Detector::new().mounter(mounter.clone()).mount_flags(MountFlags::NOEXEC);
+        match detector.detect(Path::new("/dev/os-detect-mock"), "ntfs") {
+            Ok(Some(OS::Windows { .. })) => (),
+            other => panic!("expected Windows, found {:?}", other),
+        }
+
+        let flags: Vec<_> = mounter.mounts.lock().unwrap().iter().map(|mount| mount.2).collect();
+        // Both NTFS drivers are tried read-only before falling back to read-write.
+        let read_only = MountFlags::RDONLY | MountFlags::NOEXEC;
+        assert_eq!(flags, [read_only, read_only, MountFlags::NOEXEC]);
+
+        let mounter = Arc::new(FixtureMounter::new(&[]));
+        let detector = Detector::new().mounter(mounter.clone()).read_only(false);
+        assert!(detector.detect(Path::new("/dev/os-detect-mock"), "ext4").unwrap().is_none());
+        assert_eq!(mounter.mounts.lock().unwrap()[0].2, MountFlags::empty());
+    }
+
+    #[test]
+    fn auto_detection_candidate_order() {
+        let mut tried = Vec::new();
+        let os = detect_with_candidates(&["ext4", "btrfs", "xfs", "ntfs"], |fs| {
+            tried.push(fs.to_owned());
+            match fs {
+                "ext4" => Err(DetectError::Mount {
+                    device: PathBuf::from("/dev/sda1"),
+                    fs: fs.into(),
+                    why: io::Error::from_raw_os_error(22),
+                }),
+                "btrfs" => Ok(None),
+                _ => Ok(Some(OS::ChromeOs(fs.into()))),
+            }
+        });
+
+        assert_eq!(tried, ["ext4", "btrfs", "xfs"]);
+        match os {
+            Some(OS::ChromeOs(fs)) => assert_eq!(fs, "xfs"),
+            other => panic!("expected a detection, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn detect_error_messages() {
+        let cases = vec![
+            (
+                DetectError::TempDir(io::Error::from_raw_os_error(28)),
+                "unable to create a temporary directory to mount to: ",
+            ),
+            (
+                DetectError::Mount {
+                    device: PathBuf::from("/dev/sda1"),
+                    fs: "ext4".into(),
+                    why: io::Error::from_raw_os_error(22),
+                },
+                "unable to mount \"/dev/sda1\" as ext4: ",
+            ),
+            (
+                DetectError::Io(io::Error::from_raw_os_error(5)),
+                "I/O error while probing for an OS: ",
+            ),
+            (
+                DetectError::OsRelease {
+                    path: PathBuf::from("/mnt/etc/os-release"),
+                    why: io::Error::new(io::ErrorKind::InvalidData, "not UTF-8"),
+                },
+                "unable to read the os-release at \"/mnt/etc/os-release\": not UTF-8",
+            ),
+        ];
+
+        for (error, message) in cases {
+            let source = error.source().expect("an underlying io::Error");
+            assert!(error.to_string().starts_with(message), "{}", error);
+            assert!(error.to_string().ends_with(&source.to_string()), "{}", error);
+            assert!(source.downcast_ref::<io::Error>().is_some());
+        }
+
+        let timeout = DetectError::Timeout {
+            device: PathBuf::from("/dev/sdb"),
+            timeout: Duration::from_secs(5),
+        };
+        assert_eq!(timeout.to_string(), "timed out after 5s while probing \"/dev/sdb\"");
+        assert!(timeout.source().is_none());
+
+        let boxed: Box<dyn Error> = Box::new(timeout);
+        assert!(boxed.downcast_ref::<DetectError>().is_some());
+    }
+
+    #[test]
+    fn auto_detection_tries_supported_filesystems() {
+        assert_eq!(auto_candidates(None), SUPPORTED_FILESYSTEMS);
+
+        let candidates = auto_candidates(Some("xfs"));
+        assert_eq!(candidates[0], "xfs");
+        assert_eq!(candidates.len(), SUPPORTED_FILESYSTEMS.len());
+        let mut sorted = candidates.clone();
+        sorted.sort_unstable();
+        let mut supported = SUPPORTED_FILESYSTEMS.to_vec();
+        supported.sort_unstable();
+        assert_eq!(sorted, supported);
+
+        // A filesystem that was probed, but isn't otherwise supported, is still tried first.
+        assert_eq!(auto_candidates(Some("iso9660"))[..2], ["iso9660", "ext4"]);
+    }
+
+    #[test]
+    fn probe_filesystem_from_image() {
+        let dir = fixture(&[]);
+        let image = dir.path().join("disk.img");
+        let mut data = vec![0; 0x2000];
+        data[0x438..0x43A].copy_from_slice(&[0x53, 0xEF]);
+        data[0x460] = 0x40;
+        fs::write(&image, data).unwrap();
+
+        assert_eq!(probe_filesystem(&image), Some("ext4".into()));
+        assert_eq!(probe_filesystem(&dir.path().join("missing.img")), None);
+    }
+
+    /// Stands in for a mount, and records its unmount attempts.
+    struct FakeMount {
+        unmounts: std::cell::RefCell<Vec<UnmountFlags>>,
+        busy: bool,
+        stuck: bool,
+    }
+
+    impl FakeMount {
+        fn new(busy: bool, stuck: bool) -> Self {
+            FakeMount { unmounts: Default::default(), busy, stuck }
+        }
+    }
+
+    impl Unmount for &FakeMount {
+        fn unmount(&self, flags: UnmountFlags) -> io::Result<()> {
+            self.unmounts.borrow_mut().push(flags);
+            if self.stuck || (self.busy && !flags.contains(UnmountFlags::DETACH)) {
+                Err(io::Error::from_raw_os_error(16))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn mount_guard_removes_tempdir_after_unmount() {
+        let mount = FakeMount::new(false, false);
+        let tempdir = TempDir::new("os-detect-test").unwrap();
+        let path = tempdir.path().to_path_buf();
+        drop(MountGuard::new(Box::new(&mount), tempdir));
+        assert!(!path.exists());
+        assert_eq!(*mount.unmounts.borrow(), [UnmountFlags::empty()]);
+
+        let mount = FakeMount::new(true, false);
+        let tempdir = TempDir::new("os-detect-test").unwrap();
+        let path = tempdir.path().to_path_buf();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = MountGuard::new(Box::new(&mount), tempdir);
+            panic!("detection failed");
+        }));
+
+        assert!(result.is_err());
+        assert!(!path.exists());
+        assert_eq!(*mount.unmounts.borrow(), [UnmountFlags::empty(), UnmountFlags::DETACH]);
+    }
+
+    #[test]
+    fn mount_guard_keeps_tempdir_while_mounted() {
+        let mount = FakeMount::new(true, true);
+        let tempdir = TempDir::new("os-detect-test").unwrap();
+        let file = tempdir.path().join("etc/os-release");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, POP_OS_RELEASE).unwrap();
+
+        drop(MountGuard::new(Box::new(&mount), tempdir));
+        assert!(file.exists());
+        fs::remove_dir_all(file.parent().unwrap().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn parallel_keeps_order() {
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let items: Vec<u64> = (0..10).collect();
+        let results = run_parallel(&items, 3, |&item| {
+            let now = active.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+            peak.fetch_max(now, AtomicOrdering::SeqCst);
+            thread::sleep(Duration::from_millis(20 - item * 2));
+            active.fetch_sub(1, AtomicOrdering::SeqCst);
+            item * 10
+        });
+
+        assert_eq!(results, (0..10).map(|item| item * 10).collect::<Vec<_>>());
+        assert!(peak.load(AtomicOrdering::SeqCst) > 1);
+        assert!(peak.load(AtomicOrdering::SeqCst) <= 3);
+        assert!(run_parallel(&[] as &[u64], 3, |&item| item).is_empty());
+    }
+
+    #[test]
+    fn devices_report_in_order() {
+        let devices: Vec<(PathBuf, FilesystemType)> = (0..6)
+            .map(|n| (PathBuf::from(format!("/dev/os-detect-nonexistent{}", n)), "ext4".into()))
+            .collect();
+
+        let results = detect_os_from_devices(&devices);
+        let paths: Vec<_> = results.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(paths, devices.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>());
+        assert!(results.iter().all(|(_, os)| os.is_none()));
+    }
+
+    #[test]
+    fn timeout_expires() {
+        let slow = run_with_timeout(Duration::from_millis(10), || {
+            thread::sleep(Duration::from_millis(500));
+            1
+        });
+        assert_eq!(slow, None);
+
+        assert_eq!(run_with_timeout(Duration::from_secs(5), || 2), Some(2));
+    }
+
+    #[test]
+    fn device_timeout_reports_mount_errors() {
+        let device = Path::new("/dev/os-detect-nonexistent");
+        match detect_os_from_device_timeout(device, "ext4", Duration::from_secs(5)) {
+            Err(DetectError::Mount { fs, .. }) => assert_eq!(fs, "ext4"),
+            other => panic!("expected a mount error, found {:?}", other),
+        }
+
+        let set: &[&str] = &["ext4", "btrfs"];
+        match detect_os_from_device_timeout(device, set, Duration::from_secs(5)) {
+            Err(DetectError::Mount { fs, .. }) => assert_eq!(fs, "ext4,btrfs"),
+            other => panic!("expected a mount error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn detector_defaults() {
+        let detector = Detector::default();
+        assert_eq!(detector.temp_prefix, "os-detect");
+        assert!(detector.read_only);
+        assert_eq!(detector.mount_flags, MountFlags::empty());
+        assert_eq!(detector.mount_data, None);
+        assert_eq!(detector.timeout, None);
+
+        let detector = Detector::new().read_only(false).timeout(Some(Duration::from_secs(3)));
+        assert!(!detector.read_only);
+        assert_eq!(detector.timeout, Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn detector_temp_prefix() {
+        let detector = Detector::new()
+            .temp_prefix("installer")
+            .mount_flags(MountFlags::NOEXEC)
+            .mount_data(Some("noload".into()));
+        assert_eq!(detector.temp_prefix, "installer");
+        assert_eq!(detector.mount_flags, MountFlags::NOEXEC);
+        assert_eq!(detector.mount_data, Some("noload".into()));
+    }
+
+    #[test]
+    fn device_mount_error() {
+        let device = Path::new("/dev/os-detect-nonexistent");
+        match try_detect_os_from_device(device, "ext4") {
+            Err(DetectError::Mount { device: path, fs, .. }) => {
+                assert_eq!(path, device);
+                assert_eq!(fs, "ext4");
+            }
+            other => panic!("expected a mount error, found {:?}", other),
+        }
+
+        assert!(detect_os_from_device(device, "ext4").is_none());
+
+        match detect_os_from_device_with_options(device, "ext4", Some("noload")) {
+            Err(DetectError::Mount { fs, .. }) => assert_eq!(fs, "ext4"),
+            other => panic!("expected a mount error, found {:?}", other),
+        }
+    }
+
+    const FSTAB: &str = r#"# /etc/fstab: static file system information.
+UUID=8e3b4d6e-7b4a-4c4e-9d59-1c6c5f3b2a10  /          ext4  errors=remount-ro  0  1
+PARTUUID=0b9d6c41-02                       /boot/efi  vfat  umask=0077         0  0
+LABEL=home                                 /home      ext4  defaults           0  2
+PARTLABEL=recovery                         /recovery  vfat  umask=0077         0  0
+/dev/disk/by-uuid/1f2e3d4c                 /var       xfs   defaults           0  2
+/dev/sda5                                  /srv       ext4  defaults           0  2
+"#;
+
+    #[test]
+    fn fstab_source_kinds() {
+        let fstab = parse_fstab(Cursor::new(FSTAB));
+        assert_eq!(
+            fstab.entries.iter().map(|entry| entry.source.clone()).collect::<Vec<_>>(),
+            vec![
+                PartitionID::new_uuid("8e3b4d6e-7b4a-4c4e-9d59-1c6c5f3b2a10".into()),
+                PartitionID::new_partuuid("0b9d6c41-02".into()),
+                PartitionID::new_label("home".into()),
+                PartitionID::new_partlabel("recovery".into()),
+                PartitionID::new_uuid("1f2e3d4c".into()),
+                PartitionID::new_path("/dev/sda5".into()),
+            ]
+        );
+        assert_eq!(
+            fstab.entries.iter().map(|entry| entry.target.clone()).collect::<Vec<_>>(),
+            ["/", "/boot/efi", "/home", "/recovery", "/var", "/srv"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn linux_partitions() {
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", FSTAB)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { home, efi, recovery, .. })) => {
+                assert_eq!(
+                    home,
+                    Some(MountSource::Partition {
+                        id: PartitionID::new_label("home".into()),
+                        encrypted: false
+                    })
+                );
+                assert_eq!(efi, Some(PartitionID::new_partuuid("0b9d6c41-02".into())));
+                assert_eq!(
+                    recovery,
+                    Some(MountSource::Partition {
+                        id: PartitionID::new_partlabel("recovery".into()),
+                        encrypted: false,
+                    })
+                );
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    const FEDORA_OS_RELEASE: &str = r#"NAME="Fedora Linux"
+VERSION="38 (Workstation Edition)"
+ID=fedora
+VERSION_ID=38
+PRETTY_NAME="Fedora Linux 38 (Workstation Edition)"
+VARIANT="Workstation Edition"
+VARIANT_ID=workstation
+"#;
+
+    #[test]
+    fn linux_variant() {
+        let dir = fixture(&[("etc/os-release", FEDORA_OS_RELEASE)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { variant, variant_id, .. })) => {
+                assert_eq!(variant.as_deref(), Some("Workstation Edition"));
+                assert_eq!(variant_id.as_deref(), Some("workstation"));
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let dir = fixture(&[("etc/os-release", "NAME=Fedora\nVARIANT='Server Edition'\n")]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { variant, variant_id, .. })) => {
+                assert_eq!(variant.as_deref(), Some("Server Edition"));
+                assert_eq!(variant_id, None);
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linux_encrypted_partitions() {
+        let crypttab = "# <target name> <source device> <key file> <options>\n\
+            cryptroot UUID=5a1f0c2e none luks,discard\n\
+            crypthome PARTUUID=0b9d6c41-03 /etc/keys/home.key luks\n";
+        let fstab = "/dev/mapper/cryptroot / ext4 defaults 0 1\n\
+            /dev/mapper/crypthome /home ext4 defaults 0 2\n\
+            /dev/mapper/vg-var /var ext4 defaults 0 2\n";
+        let dir = fixture(&[
+            ("etc/os-release", POP_OS_RELEASE),
+            ("etc/fstab", fstab),
+            ("etc/crypttab", crypttab),
+        ]);
+
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { home, var, root_encrypted, partitions, .. })) => {
+                assert!(root_encrypted);
+                assert_eq!(
+                    home,
+                    Some(MountSource::Partition {
+                        id: PartitionID::new_partuuid("0b9d6c41-03".into()),
+                        encrypted: true,
+                    })
+                );
+                // Mapper names which aren't in the crypttab are taken as LVM volumes.
+                assert_eq!(var, Some(MountSource::LvmVolume("vg/var".into())));
+                assert_eq!(partitions[0], PartitionID::new_uuid("5a1f0c2e".into()));
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { root_encrypted, .. })) => assert!(!root_encrypted),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linux_raw_os_release() {
+        let release = format!("{}\nLOGO=distributor-logo-pop-os\nPOP_BUILD='42'\n", POP_OS_RELEASE);
+        let dir = fixture(&[("etc/os-release", &release)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, raw, .. })) => {
+                assert_eq!(raw.get("LOGO").map(String::as_str), Some("distributor-logo-pop-os"));
+                assert_eq!(raw.get("POP_BUILD").map(String::as_str), Some("42"));
+                assert_eq!(raw.get("PRETTY_NAME"), Some(&info.pretty_name));
+                assert_eq!(raw.len(), 9);
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linux_corrupt_os_release() {
+        let dir = fixture(&[("etc/os-release", "\u{7f}ELF\u{2}\u{1}\u{1}garbage\n\u{0}\u{0}\n")]);
+        match try_detect_linux(dir.path()) {
+            Err(DetectError::OsRelease { path, why }) => {
+                assert_eq!(path, dir.path().join("etc/os-release"));
+                assert_eq!(why.kind(), io::ErrorKind::InvalidData);
+            }
+            other => panic!("expected an os-release error, found {:?}", other),
+        }
+        assert_eq!(detect_linux(dir.path()), None);
+
+        fs::write(dir.path().join("etc/os-release"), [b'I', b'D', b'=', 0xff, b'\n']).unwrap();
+        assert!(try_detect_linux(dir.path()).is_err());
+
+        let dir = fixture(&[("etc/os-release", "# No keys\n\n")]);
+        assert!(try_detect_linux(dir.path()).unwrap().is_some());
+
+        // A stray line among the assignments is skipped.
+        let release = format!("a note left by hand\n{}", POP_OS_RELEASE);
+        let dir = fixture(&[("etc/os-release", &release)]);
+        match try_detect_linux(dir.path()) {
+            Ok(Some(OS::Linux(LinuxInstall { info, .. }))) => {
+                assert_eq!((info.name.as_str(), info.id.as_str()), ("Pop!_OS", "ubuntu"))
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let dir = fixture(&[("etc/fstab", FSTAB)]);
+        assert!(try_detect_linux(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn linux_ostree_deployment() {
+        use std::os::unix::fs::symlink;
+
+        let deploy = "ostree/deploy/fedora/deploy";
+        let silverblue = "NAME=\"Fedora Linux\"\nVERSION_ID=39\nVARIANT_ID=silverblue\n";
+        let dir = fixture(&[
+            (
+                &format!("{}/1a2b.0/etc/os-release", deploy),
+                "NAME=\"Fedora Linux\"\nVERSION_ID=38\n",
+            ),
+            (&format!("{}/1a2b.0.origin", deploy), ""),
+            (&format!("{}/3c4d.0/etc/os-release", deploy), silverblue),
+            (
+                "boot/loader/entries/ostree-1-fedora.conf",
+                "version 1\noptions ostree=/ostree/boot.1/fedora/aa/1",
+            ),
+            (
+                "boot/loader/entries/ostree-2-fedora.conf",
+                "version 2\noptions ostree=/ostree/boot.1/fedora/bb/0",
+            ),
+        ]);
+
+        let boot = dir.path().join("ostree/boot.1.1/fedora");
+        fs::create_dir_all(boot.join("aa")).unwrap();
+        fs::create_dir_all(boot.join("bb")).unwrap();
+        symlink("boot.1.1", dir.path().join("ostree/boot.1")).unwrap();
+        symlink("../../../deploy/fedora/deploy/1a2b.0", boot.join("aa/1")).unwrap();
+        symlink("../../../deploy/fedora/deploy/3c4d.0", boot.join("bb/0")).unwrap();
+
+        match detect_os_from_path(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, ostree, .. })) => {
+                assert_eq!(info.version_id, "39");
+                assert!(ostree);
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let dir = fixture(&[(&format!("{}/1a2b.0/etc/os-release", deploy), silverblue)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, ostree, .. })) => {
+                assert_eq!(info.version_id, "39");
+                assert!(ostree);
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        // Without a usable boot loader entry, the deployments with an origin are preferred,
+        // then the highest serial among them.
+        let outside = fixture(&[("etc/os-release", "NAME=\"Fedora Linux\"\nVERSION_ID=40\n")]);
+        let dir = fixture(&[
+            (
+                &format!("{}/0a0a.0/etc/os-release", deploy),
+                "NAME=\"Fedora Linux\"\nVERSION_ID=37\n",
+            ),
+            (
+                &format!("{}/1a2b.0/etc/os-release", deploy),
+                "NAME=\"Fedora Linux\"\nVERSION_ID=38\n",
+            ),
+            (&format!("{}/1a2b.0.origin", deploy), ""),
+            (&format!("{}/1a2b.1/etc/os-release", deploy), silverblue),
+            (&format!("{}/1a2b.1.origin", deploy), ""),
+            (
+                "boot/loader/entries/ostree-1-fedora.conf",
+                "version 1\noptions ostree=/ostree/boot.1/fedora/aa/0",
+            ),
+        ]);
+        fs::create_dir_all(dir.path().join("ostree/boot.1/fedora/aa")).unwrap();
+        symlink(outside.path(), dir.path().join("ostree/boot.1/fedora/aa/0")).unwrap();
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, .. })) => assert_eq!(info.version_id, "39"),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { ostree, .. })) => assert!(!ostree),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linux_security_modules() {
+        let security_module = |files: &[(&str, &str)]| detect_pop_os(files).security_module;
+
+        let selinux = |mode: &str| format!("SELINUX={}\nSELINUXTYPE=targeted\n", mode);
+        let (enforcing, permissive) = (selinux("enforcing"), selinux("permissive"));
+        let disabled = selinux("disabled");
+        assert_eq!(
+            security_module(&[("etc/selinux/config", &enforcing)]),
+            Some(SecurityModule::SeLinux { enforcing: true })
+        );
+        assert_eq!(
+            security_module(&[("etc/selinux/config", &permissive)]),
+            Some(SecurityModule::SeLinux { enforcing: false })
+        );
+
+        let parser_conf = ("etc/apparmor/parser.conf", "");
+        let apparmor = [parser_conf, ("etc/apparmor.d/usr.bin.man", "profile man {}")];
+        assert_eq!(security_module(&apparmor), Some(SecurityModule::AppArmor));
+        let mut disabled_selinux = apparmor.to_vec();
+        disabled_selinux.push(("etc/selinux/config", &disabled));
+        assert_eq!(security_module(&disabled_selinux), Some(SecurityModule::AppArmor));
+
+        // AppArmor's userspace without any profiles, and an empty system.
+        let tunables = ("etc/apparmor.d/tunables/global", "");
+        assert_eq!(security_module(&[parser_conf, tunables]), None);
+        assert_eq!(security_module(&[]), None);
+    }
+
+    #[test]
+    fn linux_package_managers() {
+        let package_manager = |files: &[(&str, &str)]| detect_pop_os(files).package_manager;
+
+        let dpkg = [("var/lib/dpkg/status", "Package: bash\nStatus: install ok installed\n")];
+        assert_eq!(package_manager(&dpkg), Some(PackageManager::Dpkg));
+        let rpm = [("var/lib/rpm/rpmdb.sqlite", "")];
+        assert_eq!(package_manager(&rpm), Some(PackageManager::Rpm));
+        let sysimage = [("usr/lib/sysimage/rpm/rpmdb.sqlite", "")];
+        assert_eq!(package_manager(&sysimage), Some(PackageManager::Rpm));
+        let pacman = [("var/lib/pacman/local/ALPM_DB_VERSION", "9")];
+        assert_eq!(package_manager(&pacman), Some(PackageManager::Pacman));
+        assert_eq!(package_manager(&[("etc/apk/world", "")]), Some(PackageManager::Apk));
+        // Debian's dpkg directory without its status database.
+        assert_eq!(package_manager(&[("var/lib/dpkg/lock", "")]), None);
+    }
+
+    #[test]
+    fn linux_init_systems() {
+        let init_system = |files: &[(&str, &str)]| detect_pop_os(files).init_system;
+
+        let systemd = [("usr/lib/systemd/systemd", ""), ("etc/init.d/cron", ""), ("sbin/init", "")];
+        assert_eq!(init_system(&systemd), Some(InitSystem::Systemd));
+        assert_eq!(init_system(&[("lib/systemd/systemd", "")]), Some(InitSystem::Systemd));
+
+        let openrc = [("sbin/openrc", ""), ("etc/init.d/sshd", ""), ("sbin/init", "")];
+        assert_eq!(init_system(&openrc), Some(InitSystem::OpenRc));
+
+        let sysvinit = [("etc/init.d/rc", ""), ("sbin/init", ""), ("etc/inittab", "")];
+        assert_eq!(init_system(&sysvinit), Some(InitSystem::SysVinit));
+
+        assert_eq!(init_system(&[("etc/init.d/rc", "")]), None);
+    }
+
+    #[test]
+    fn linux_usr_lib_os_release() {
+        let dir = fixture(&[("usr/lib/os-release", POP_OS_RELEASE)]);
+        match detect_os_from_path(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, source, .. })) => {
+                assert_eq!(info.pretty_name, "Pop!_OS 18.04 LTS");
+                assert_eq!(source, OsReleaseSource::UsrLib);
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        // An absolute link must not lead to the os-release of the host.
+        let dir = fixture(&[("usr/lib/os-release", "NAME=\"Fedora Linux\"\n")]);
+        fs::create_dir(dir.path().join("etc")).unwrap();
+        std::os::unix::fs::symlink("/usr/lib/os-release", dir.path().join("etc/os-release"))
+            .unwrap();
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, source, .. })) => {
+                assert_eq!(info.name, "Fedora Linux");
+                assert_eq!(source, OsReleaseSource::Etc);
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let dir =
+            fixture(&[("etc/os-release", POP_OS_RELEASE), ("usr/lib/os-release", "NAME=Other\n")]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, .. })) => assert_eq!(info.name, "Pop!_OS"),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "initramfs")]
+    #[test]
+    fn linux_initramfs_os_release() {
+        use initramfs::tests::{cpio, gzip};
+
+        let dir = fixture(&[("boot/vmlinuz-6.2.0-39-generic", ""), ("sbin/init", "")]);
+        let initramfs = gzip(&cpio(&[("usr/lib/os-release", 0o100644, POP_OS_RELEASE)]));
+        fs::write(dir.path().join("boot/initrd.img-6.2.0-39-generic"), initramfs).unwrap();
+        let stale = gzip(&cpio(&[("etc/os-release", 0o100644, "NAME=Stale\n")]));
+        fs::write(dir.path().join("boot/initrd.img-5.19.0-50-generic"), stale).unwrap();
+
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { info, source, kernels, .. })) => {
+                assert_eq!(info.pretty_name, "Pop!_OS 18.04 LTS");
+                assert_eq!(source, OsReleaseSource::Initramfs);
+                assert_eq!(kernels, ["6.2.0-39-generic"]);
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let dir = fixture(&[("boot/initrd.img-6.2.0-39-generic", "not an initramfs")]);
+        assert_eq!(detect_linux(dir.path()), None);
+    }
+
+    /// The start of an ELF header, up to its machine.
+    fn elf_header(class: u8, data: u8, machine: u16) -> Vec<u8> {
+        let mut header = b"\x7fELF".to_vec();
+        header.extend_from_slice(&[class, data, 1, 0]);
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&[2, 0]);
+        match data {
+            2 => header.extend_from_slice(&machine.to_be_bytes()),
+            _ => header.extend_from_slice(&machine.to_le_bytes()),
+        }
+
+        header
+    }
+
+    #[test]
+    fn elf_architectures() {
+        assert_eq!(elf_arch(&elf_header(2, 1, 62)), Some(Arch::X86_64));
+        assert_eq!(elf_arch(&elf_header(1, 1, 3)), Some(Arch::X86));
+        assert_eq!(elf_arch(&elf_header(2, 1, 183)), Some(Arch::Aarch64));
+        assert_eq!(elf_arch(&elf_header(1, 1, 40)), Some(Arch::Arm));
+        assert_eq!(elf_arch(&elf_header(2, 2, 22)), Some(Arch::S390x));
+        assert_eq!(elf_arch(&elf_header(2, 1, 243)), Some(Arch::Riscv64));
+        assert_eq!(elf_arch(&elf_header(3, 1, 62)), None);
+        assert_eq!(elf_arch(&elf_header(2, 1, 62)[..16]), None);
+        assert_eq!(elf_arch(b"#!/bin/sh\nexec /usr/lib/systemd\n"), None);
+        assert!(Arch::X86_64.is_64_bit());
+        assert!(!Arch::X86.is_64_bit());
+    }
+
+    #[test]
+    fn linux_arch() {
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        fs::create_dir_all(dir.path().join("usr/bin")).unwrap();
+        fs::write(dir.path().join("usr/bin/bash"), elf_header(2, 1, 62)).unwrap();
+        std::os::unix::fs::symlink("usr/bin", dir.path().join("bin")).unwrap();
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { arch, .. })) => assert_eq!(arch, Some(Arch::X86_64)),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("usr/bin/bash", "not an ELF")]);
+        fs::create_dir_all(dir.path().join("sbin")).unwrap();
+        fs::write(dir.path().join("sbin/init"), elf_header(1, 1, 3)).unwrap();
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { arch, .. })) => assert_eq!(arch, Some(Arch::X86)),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linux_bootable() {
+        let is_bootable = |files: &[(&str, &str)]| detect_pop_os(files).is_bootable;
+
+        let install = [
+            ("boot/vmlinuz-6.5.0-10043-generic", ""),
+            ("boot/initrd.img-6.5.0-10043-generic", ""),
+            ("usr/lib/systemd/systemd", ""),
+        ];
+        assert!(is_bootable(&install));
+
+        let separate_boot =
+            [("etc/fstab", "UUID=a1b2 /boot ext4 defaults 0 2\n"), ("sbin/openrc", "")];
+        assert!(is_bootable(&separate_boot));
+
+        // A container image, which has no kernel, and usually no init system.
+        assert!(!is_bootable(&[("usr/bin/bash", ""), ("etc/hostname", "container\n")]));
+        assert!(!is_bootable(&[("usr/lib/systemd/systemd", "")]));
+        assert!(!is_bootable(&[("boot/vmlinuz-6.5.0-10043-generic", "")]));
+    }
+
+    #[test]
+    fn linux_boot_mode() {
+        let esp = "UUID=b7e1 / ext4 defaults 0 1\nUUID=4A1F-22B0 /boot/efi vfat umask=0077 0 1\n";
+        let bios = ("boot/grub/i386-pc/normal.mod", "");
+        let boot_mode = |files: &[(&str, &str)]| detect_pop_os(files).boot_mode;
+
+        assert_eq!(boot_mode(&[("etc/fstab", esp)]), BootMode::Uefi);
+        assert_eq!(boot_mode(&[bios]), BootMode::Bios);
+        assert_eq!(boot_mode(&[("boot/grub2/i386-pc/normal.mod", "")]), BootMode::Bios);
+        assert_eq!(boot_mode(&[("etc/fstab", esp), bios]), BootMode::Hybrid);
+        assert_eq!(boot_mode(&[("boot/grub/x86_64-efi/normal.mod", "")]), BootMode::Unknown);
+    }
+
+    #[test]
+    fn linux_desktop() {
+        let desktop = |files: &[(&str, &str)], manager: Option<&str>| {
+            let link = |root: &Path| {
+                if let Some(manager) = manager {
+                    let service = root.join("etc/systemd/system/display-manager.service");
+                    fs::create_dir_all(service.parent().unwrap()).unwrap();
+                    std::os::unix::fs::symlink(manager, service).unwrap();
+                }
+            };
+
+            detect_pop_os_with(files, link).desktop
+        };
+
+        let gnome = ("usr/bin/gnome-session", "");
+        let plasma = ("usr/bin/startplasma-wayland", "");
+        let xfce = ("usr/bin/startxfce4", "");
+        assert_eq!(desktop(&[gnome], None), Some("GNOME".into()));
+        assert_eq!(
+            desktop(&[plasma, ("usr/bin/startplasma-x11", "")], None),
+            Some("KDE Plasma".into())
+        );
+
+        let sddm = Some("/usr/lib/systemd/system/sddm.service");
+        assert_eq!(desktop(&[gnome, plasma], sddm), Some("KDE Plasma".into()));
+        assert_eq!(desktop(&[gnome, plasma], None), None);
+
+        let lightdm = Some("/lib/systemd/system/lightdm.service");
+        assert_eq!(desktop(&[gnome, xfce], lightdm), None);
+        assert_eq!(desktop(&[xfce], lightdm), Some("Xfce".into()));
+        assert_eq!(desktop(&[], None), None);
+    }
+
+    #[test]
+    fn linux_var_partition() {
+        let fstab = "UUID=4fa1 / ext4 defaults 0 1\nUUID=77c2 /var ext4 defaults 0 2\n";
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { var, home, .. })) => {
+                assert_eq!(
+                    var,
+                    Some(MountSource::Partition {
+                        id: PartitionID::new_uuid("77c2".into()),
+                        encrypted: false
+                    })
+                );
+                assert_eq!(home, None);
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fstab_btrfs_subvolumes() {
+        let fstab = "UUID=b7e1 /     btrfs defaults,subvol=@     0 0\n\
+                     UUID=b7e1 /home btrfs defaults,subvol=/@home 0 0\n\
+                     UUID=9c02 /var  btrfs subvol=@var,compress=zstd 0 0\n";
+        let fstab = parse_fstab(Cursor::new(fstab));
+        assert_eq!(fstab.mount_source("/home"), Some(MountSource::Subvolume("@home".into())));
+        assert_eq!(
+            fstab.mount_source("/var"),
+            Some(MountSource::Partition {
+                id: PartitionID::new_uuid("9c02".into()),
+                encrypted: false
+            })
+        );
+        assert_eq!(fstab.mount_source("/recovery"), None);
+    }
+
+    #[test]
+    fn linux_machine_id() {
+        let machine_id = |files: &[(&str, &str)]| match detect_linux(fixture(files).path()) {
+            Some(OS::Linux(LinuxInstall { machine_id, .. })) => machine_id,
+            other => panic!("expected Linux, found {:?}", other),
+        };
+
+        let valid = "4c4c45440039581080324ac04f4e4a32";
+        assert_eq!(
+            machine_id(&[
+                ("etc/os-release", POP_OS_RELEASE),
+                ("etc/machine-id", &format!("{}\n", valid))
+            ]),
+            Some(valid.to_owned())
+        );
+        assert_eq!(
+            machine_id(&[
+                ("etc/os-release", POP_OS_RELEASE),
+                ("etc/machine-id", ""),
+                ("var/lib/dbus/machine-id", valid)
+            ]),
+            Some(valid.to_owned())
+        );
+        assert_eq!(
+            machine_id(&[
+                ("etc/os-release", POP_OS_RELEASE),
+                ("etc/machine-id", "uninitialized\n")
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn linux_hostname() {
+        let hostname = |files: &[(&str, &str)]| detect_pop_os(files).hostname;
+
+        let hosts = "127.0.0.1\tlocalhost\n127.0.1.1\tpop-os.localdomain pop-os\n";
+        assert_eq!(hostname(&[("etc/hostname", "pop-os\n")]), Some("pop-os".into()));
+        assert_eq!(hostname(&[("etc/hostname", "\n")]), None);
+        assert_eq!(hostname(&[("etc/hosts", hosts)]), Some("pop-os.localdomain".into()));
+        assert_eq!(hostname(&[]), None);
+    }
+
+    #[test]
+    fn os_release_values() {
+        let cases = [
+            (r#""Pop!_OS 22.04 LTS""#, "Pop!_OS 22.04 LTS"),
+            ("'Fedora Linux 39'", "Fedora Linux 39"),
+            ("ubuntu", "ubuntu"),
+            (" ubuntu \r", "ubuntu"),
+            (r#""C:\\Program Files""#, r"C:\Program Files"),
+            (r#""say \"hi\" for \$5 \`now\`""#, r#"say "hi" for $5 `now`"#),
+            (r#""\n is kept""#, r"\n is kept"),
+            (r"'\\ is literal'", r"\\ is literal"),
+            (r"unquoted\ space\\", r"unquoted space\"),
+            (r#""joined"' parts'"#, "joined parts"),
+            (r#""""#, ""),
+            (r#""unterminated"#, "unterminated"),
+        ];
+
+        for &(raw, unquoted) in &cases {
+            assert_eq!(unquote_os_release_value(raw), unquoted, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn registered_detectors() {
+        register_detector(Box::new(|base| {
+            let release = fs::read_to_string(base.join("ReactOS/system32/reactos-release")).ok()?;
+            Some(OS::Unknown { hints: vec![release.trim().to_owned()] })
+        }));
+
+        let dir = fixture(&[("ReactOS/system32/reactos-release", "ReactOS 0.4.14\n")]);
+        let os = detect_os_from_path(dir.path());
+        assert_eq!(os, Some(OS::Unknown { hints: vec!["ReactOS 0.4.14".into()] }));
+
+        // Built-in detectors take precedence, and others are unaffected.
+        let dir = fixture(&[
+            ("ReactOS/system32/reactos-release", "ReactOS 0.4.14\n"),
+            ("etc/os-release", POP_OS_RELEASE),
+        ]);
+        let found = detect_all(dir.path());
+        let names: Vec<&str> = found.iter().map(OS::name).collect();
+        assert_eq!(names, ["Pop!_OS", "Unknown"]);
+        assert_eq!(detect_all(fixture(&[("etc/os-release", POP_OS_RELEASE)]).path()).len(), 1);
+    }
+
+    #[test]
+    fn specific_detectors() {
+        let windows = ("Windows/System32/ntoskrnl.exe", "");
+        let both = fixture(&[windows, ("etc/os-release", POP_OS_RELEASE)]);
+        let name = |base: &Path, which: &[OsKind]| {
+            detect_specific(base, which).map(|os| os.name().to_owned())
+        };
+
+        assert_eq!(name(both.path(), &[OsKind::Windows]).as_deref(), Some("Windows"));
+        assert_eq!(name(both.path(), &[OsKind::Linux]).as_deref(), Some("Pop!_OS"));
+        assert_eq!(
+            name(both.path(), &[OsKind::Windows, OsKind::Linux]).as_deref(),
+            Some("Windows")
+        );
+        assert_eq!(name(both.path(), &[OsKind::MacOs, OsKind::Linux]).as_deref(), Some("Pop!_OS"));
+        assert_eq!(name(both.path(), &[]), None);
+
+        // The os-release would be found, and reported as Linux, if its detector were run.
+        let linux = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        assert_eq!(name(linux.path(), &[OsKind::Windows]), None);
+        assert_eq!(name(linux.path(), &[OsKind::Windows, OsKind::MacOs, OsKind::Bsd]), None);
+    }
+
+    #[test]
+    fn prefixed_paths() {
+        let dir = fixture(&[("@/etc/os-release", POP_OS_RELEASE), ("outside/etc/os-release", "")]);
+        let mount = dir.path().join("mount");
+        fs::create_dir(&mount).unwrap();
+        fs::rename(dir.path().join("@"), mount.join("@")).unwrap();
+        std::os::unix::fs::symlink("../../outside", mount.join("@/escape")).unwrap();
+
+        assert_eq!(join_within(&mount, Path::new("@")), Some(mount.join("@")));
+        assert_eq!(join_within(&mount, Path::new("/@/./etc")), Some(mount.join("@/etc")));
+        assert_eq!(join_within(&mount, Path::new("")), Some(mount.clone()));
+        assert_eq!(join_within(&mount, Path::new("../outside")), None);
+        assert_eq!(join_within(&mount, Path::new("@/../../outside")), None);
+        assert_eq!(join_within(&mount, Path::new("@/escape")), None);
+        // Paths which don't exist can't be resolved, but neither can they escape.
+        assert_eq!(join_within(&mount, Path::new("missing")), Some(mount.join("missing")));
+
+        let os = detect_os_from_path_prefixed(&mount, Path::new("@")).unwrap();
+        assert_eq!(os.name(), "Pop!_OS");
+        assert_eq!(detect_os_from_path_prefixed(&mount, Path::new("@/escape")), None);
+    }
+
+    #[test]
+    fn linux_theming() {
+        let fedora = "NAME=\"Fedora Linux\"\nID=fedora\nVERSION_ID=39\n\
+            ANSI_COLOR=\"0;38;2;60;110;180\"\nLOGO=fedora-logo-icon\n";
+        let theming =
+            |release: &str| match detect_linux(fixture(&[("etc/os-release", release)]).path()) {
+                Some(OS::Linux(LinuxInstall { ansi_color, logo, .. })) => (ansi_color, logo),
+                other => panic!("expected Linux, found {:?}", other),
+            };
+
+        let expected = (Some("0;38;2;60;110;180".to_owned()), Some("fedora-logo-icon".to_owned()));
+        assert_eq!(theming(fedora), expected);
+        assert_eq!(theming(POP_OS_RELEASE), (None, None));
+    }
+
+    #[test]
+    fn linux_id_like() {
+        let dir = fixture(&[("etc/os-release", POP_2204_OS_RELEASE)]);
+        let os = detect_linux(dir.path()).unwrap();
+        match os {
+            OS::Linux(LinuxInstall { ref id_like, .. }) => {
+                assert_eq!(id_like, &["ubuntu", "debian"])
+            }
+            ref other => panic!("expected Linux, found {:?}", other),
+        }
+
+        assert!(os.is_like("debian"));
+        assert!(os.is_like("ubuntu"));
+        assert!(os.is_like("pop"));
+        assert!(!os.is_like("fedora"));
+        assert!(!os.is_like("deb"));
+        assert!(!OS::Android("Android 14".into()).is_like("debian"));
+    }
+
+    #[test]
+    fn linux_timezone() {
+        let timezone = |files: &[(&str, &str)], localtime: Option<&str>| {
+            let link = |root: &Path| {
+                if let Some(target) = localtime {
+                    std::os::unix::fs::symlink(target, root.join("etc/localtime")).unwrap();
+                }
+            };
+
+            detect_pop_os_with(files, link).timezone
+        };
+
+        let london = Some("Europe/London".to_owned());
+        assert_eq!(timezone(&[], Some("../usr/share/zoneinfo/Europe/London")), london);
+        assert_eq!(timezone(&[], Some("/usr/share/zoneinfo/posix/Europe/London")), london);
+        assert_eq!(timezone(&[("etc/timezone", "Europe/London\n")], None), london);
+        assert_eq!(
+            timezone(
+                &[("etc/timezone", "Etc/UTC\n")],
+                Some("/usr/share/zoneinfo/America/New_York")
+            ),
+            Some("America/New_York".into())
+        );
+        assert_eq!(timezone(&[("etc/localtime", "TZif2")], None), None);
+    }
+
+    #[test]
+    fn linux_locale() {
+        let locale = |files: &[(&str, &str)]| detect_pop_os(files).locale;
+
+        let british = Some("en_GB.UTF-8".to_owned());
+        let locale_conf = ("etc/locale.conf", "LANG=en_GB.UTF-8\nLC_TIME=C\n");
+        let default_locale = ("etc/default/locale", "# Generated\nLANG=\"en_GB.UTF-8\"\n");
+        let environment = ("etc/environment", "PATH=\"/usr/bin\"\nexport LANG='de_DE.UTF-8'\n");
+
+        assert_eq!(locale(&[locale_conf]), british);
+        assert_eq!(locale(&[default_locale]), british);
+        assert_eq!(locale(&[environment]), Some("de_DE.UTF-8".into()));
+        assert_eq!(locale(&[environment, locale_conf]), british);
+        assert_eq!(locale(&[("etc/locale.conf", "LC_ALL=C\n"), environment]), None);
+        assert_eq!(locale(&[]), None);
+    }
+
+    #[test]
+    fn linux_kernels() {
+        let dir = fixture(&[
+            ("etc/os-release", POP_OS_RELEASE),
+            ("boot/vmlinuz-5.15.0-91-generic", ""),
+            ("boot/vmlinuz-5.4.0-150-generic", ""),
+            ("boot/vmlinuz-5.15.0-100-generic", ""),
+            ("boot/initrd.img-5.15.0-100-generic", ""),
+            ("lib/modules/5.15.0-100-generic/modules.dep", ""),
+            ("lib/modules/6.2.0-39-generic/modules.dep", ""),
+        ]);
+
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { kernels, .. })) => assert_eq!(
+                kernels,
+                [
+                    "5.4.0-150-generic",
+                    "5.15.0-91-generic",
+                    "5.15.0-100-generic",
+                    "6.2.0-39-generic"
+                ]
+            ),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn version_ordering() {
+        assert_eq!(compare_versions("5.9", "5.10"), Ordering::Less);
+        assert_eq!(compare_versions("5.10.0", "5.10"), Ordering::Greater);
+        assert_eq!(compare_versions("6.1-rc1", "6.1-rc1"), Ordering::Equal);
+    }
+
+    #[test]
+    fn linux_root_partition() {
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        let mut os = detect_os_from_path(dir.path()).unwrap();
+        match os {
+            OS::Linux(LinuxInstall { ref root, .. }) => assert_eq!(*root, None),
+            ref other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let id = PartitionID::new_uuid("8e3b4d6e-7b4a-4c4e-9d59-1c6c5f3b2a10".into());
+        set_root(&mut os, Some(id.clone()));
+        match os {
+            OS::Linux(LinuxInstall { root, .. }) => assert_eq!(root, Some(id)),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        assert_eq!(device_id(Path::new("/dev/os-detect-nonexistent")), None);
+    }
+
+    #[test]
+    fn linux_at_mountpoint() {
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        assert_eq!(detect_os_at_mountpoint(dir.path(), None), detect_os_from_path(dir.path()));
+
+        let device = Path::new("/dev/os-detect-nonexistent");
+        match detect_os_at_mountpoint(dir.path(), Some(device)) {
+            Some(OS::Linux(LinuxInstall { root, .. })) => assert_eq!(root, None),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        assert_eq!(detect_os_at_mountpoint(fixture(&[]).path(), Some(device)), None);
+    }
+
+    #[test]
+    fn fstab_swap() {
+        let fstab = "UUID=4fa1 / ext4 defaults 0 1\n\
+                     UUID=5d2e-88 none swap sw 0 0\n\
+                     /dev/sdb2 swap swap defaults 0 0\n\
+                     LABEL=scratch /swap ext4 defaults 0 0\n";
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { swap, .. })) => assert_eq!(
+                swap,
+                [
+                    PartitionID::new_uuid("5d2e-88".into()),
+                    PartitionID::new_path("/dev/sdb2".into())
+                ]
+            ),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linux_network_config() {
+        let network_config = |files: &[(&str, &str)]| detect_pop_os(files).network_config;
+
+        let debian = network_config(&[
+            ("etc/network/interfaces", "source /etc/network/interfaces.d/*\n"),
+            ("etc/network/interfaces.d/eth0", "auto eth0\niface eth0 inet static\n"),
+            ("etc/network/if-up.d/ethtool", "#!/bin/sh\n"),
+        ]);
+        assert_eq!(
+            debian,
+            [Path::new("etc/network/interfaces"), Path::new("etc/network/interfaces.d/eth0")]
+        );
+
+        let ubuntu = network_config(&[
+            ("etc/netplan/01-netcfg.yaml", "network:\n  version: 2\n"),
+            ("etc/netplan/01-netcfg.yaml.bak", ""),
+            ("etc/NetworkManager/system-connections/Home Wi-Fi.nmconnection", "[connection]\n"),
+        ]);
+        assert_eq!(
+            ubuntu,
+            [
+                Path::new("etc/netplan/01-netcfg.yaml"),
+                Path::new("etc/NetworkManager/system-connections/Home Wi-Fi.nmconnection"),
+            ]
+        );
+
+        let rhel = network_config(&[
+            ("etc/sysconfig/network-scripts/ifcfg-eth0", "BOOTPROTO=static\n"),
+            ("etc/sysconfig/network-scripts/ifup-eth", "#!/bin/bash\n"),
+        ]);
+        assert_eq!(rhel, [Path::new("etc/sysconfig/network-scripts/ifcfg-eth0")]);
+        assert!(network_config(&[]).is_empty());
+    }
+
+    #[test]
+    fn linux_last_seen() {
+        let day = |days: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(days * 86_400);
+        let modified = [
+            ("etc/os-release", day(19_700)),
+            ("var/log/wtmp", day(19_702)),
+            ("home/alice/.bash_history", day(19_707)),
+            ("home/bob/.local/share/recently-used.xbel", day(19_705)),
+        ];
+
+        let files: Vec<(&str, &str)> = modified
+            .iter()
+            .map(|&(path, _)| (path, if path == "etc/os-release" { POP_OS_RELEASE } else { "" }))
+            .collect();
+        let dir = fixture(&files);
+        for &(path, time) in &modified {
+            let file = File::options().write(true).open(dir.path().join(path)).unwrap();
+            file.set_modified(time).unwrap();
+        }
+
+        let detected = detect_linux(dir.path());
+        match detected {
+            Some(OS::Linux(LinuxInstall { last_seen, .. })) => {
+                assert_eq!(last_seen.0, Some(day(19_707)))
+            }
+            ref other => panic!("expected Linux, found {:?}", other),
+        }
+
+        // The same install is still equal to itself once it has been used again.
+        let history = dir.path().join("home/alice/.bash_history");
+        File::options().write(true).open(history).unwrap().set_modified(day(19_710)).unwrap();
+        assert_eq!(detect_linux(dir.path()), detected);
+        assert_eq!(last_seen(fixture(&[]).path()), None);
+    }
+
+    #[test]
+    fn fstab_root_filesystem() {
+        let root_fs = |fstab: &str| {
+            let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+            match detect_linux(dir.path()) {
+                Some(OS::Linux(LinuxInstall { root_fs, part_refs, .. })) => {
+                    (root_fs, part_refs.get(Path::new("/")).and_then(|root| root.uuid.clone()))
+                }
+                other => panic!("expected Linux, found {:?}", other),
+            }
+        };
+
+        let btrfs = "UUID=e1c0 / btrfs subvol=@,compress=zstd:1 0 0\n\
+                     UUID=e1c0 /home btrfs subvol=@home 0 0\n";
+        assert_eq!(root_fs(btrfs), (Some("btrfs".into()), Some("e1c0".into())));
+        assert_eq!(root_fs("UUID=4fa1 / auto defaults 0 1\n"), (None, Some("4fa1".into())));
+        assert_eq!(root_fs("UUID=4fa1 /home ext4 defaults 0 2\n"), (None, None));
+    }
+
+    #[test]
+    fn fstab_swapfile() {
+        let swap = |fstab: &str| {
+            let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+            match detect_linux(dir.path()) {
+                Some(OS::Linux(LinuxInstall { swap, swapfile, .. })) => (swap, swapfile),
+                other => panic!("expected Linux, found {:?}", other),
+            }
+        };
+
+        let fstab = "UUID=4fa1 / ext4 defaults 0 1\n\
+                     /swapfile none swap sw 0 0\n\
+                     /dev/sdb2 none swap defaults 0 0\n";
+        let partition = PartitionID::new_path("/dev/sdb2".into());
+        assert_eq!(swap(fstab), (vec![partition], Some("/swapfile".into())));
+        assert_eq!(swap("/var/swap none swap sw 0 0\n"), (Vec::new(), Some("/var/swap".into())));
+        assert_eq!(swap("UUID=4fa1 / ext4 defaults 0 1\n"), (Vec::new(), None));
+    }
+
+    #[test]
+    fn efi_bootloaders() {
+        let bootloader = |files: &[(&str, &str)]| detect_bootloader(fixture(files).path());
+        let windows = ("EFI/Microsoft/Boot/bootmgfw.efi", "");
+
+        assert_eq!(
+            bootloader(&[("EFI/systemd/systemd-bootx64.efi", ""), windows]),
+            Some(Bootloader::SystemdBoot)
+        );
+        assert_eq!(bootloader(&[("EFI/ubuntu/grubx64.efi", ""), windows]), Some(Bootloader::Grub));
+        assert_eq!(bootloader(&[("EFI/grub/grubx64.efi", "")]), Some(Bootloader::Grub));
+        assert_eq!(bootloader(&[windows]), Some(Bootloader::WindowsBootManager));
+        assert_eq!(bootloader(&[("EFI/Boot/bootx64.efi", "")]), None);
+    }
+
+    #[test]
+    fn linux_bootloader() {
+        let dir = fixture(&[
+            ("etc/os-release", POP_OS_RELEASE),
+            ("boot/efi/EFI/systemd/systemd-bootx64.efi", ""),
+        ]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { bootloader, .. })) => {
+                assert_eq!(bootloader, Some(Bootloader::SystemdBoot))
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linux_resolved_efi_partition() {
+        let root = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", FSTAB)]);
+        let efi = fixture(&[("EFI/systemd/systemd-bootx64.efi", "")]);
+
+        let resolve = |id: &PartitionID| match id.id.as_str() {
+            "0b9d6c41-02" => Some(efi.path().to_path_buf()),
+            _ => None,
+        };
+
+        match detect_linux_with_resolver(root.path(), resolve).unwrap() {
+            Some(OS::Linux(LinuxInstall { bootloader, .. })) => {
+                assert_eq!(bootloader, Some(Bootloader::SystemdBoot))
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        assert_eq!(
+            detect_linux_with_resolver(root.path(), |_| None).unwrap(),
+            detect_linux(root.path())
+        );
+        match detect_linux(root.path()) {
+            Some(OS::Linux(LinuxInstall { bootloader, .. })) => assert_eq!(bootloader, None),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn linux_recovery_contents() {
+        let root = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", FSTAB)]);
+        let recovery = fixture(&[
+            ("recovery.conf", "HOSTNAME=pop-os\nLANG=en_US.UTF-8\nRECOVERY_UUID=5d2a-11f4\n"),
+            ("casper-5d2a-11f4/filesystem.squashfs", ""),
+            ("casper-5d2a-11f4/vmlinuz.efi", ""),
+        ]);
+        let empty = fixture(&[("lost+found/.keep", "")]);
+
+        let valid = |mounted: &Path| {
+            let resolve = |id: &PartitionID| match id.id.as_str() {
+                "recovery" => Some(mounted.to_path_buf()),
+                _ => None,
+            };
+
+            match detect_linux_with_resolver(root.path(), resolve).unwrap() {
+                Some(OS::Linux(LinuxInstall { recovery_valid, recovery_image, .. })) => {
+                    assert_eq!(recovery_valid, recovery_image.is_some());
+                    recovery_valid
+                }
+                other => panic!("expected Linux, found {:?}", other),
+            }
+        };
+
+        assert!(valid(recovery.path()));
+        assert!(!valid(empty.path()));
+        match detect_linux(root.path()) {
+            Some(OS::Linux(LinuxInstall { recovery_valid, .. })) => assert!(!recovery_valid),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pop_recovery_image() {
+        let conf = "HOSTNAME=pop-os\nLANG=en_US.UTF-8\nKBD_LAYOUT=us\n\
+            EFI_UUID=PARTUUID=0b9d6c41-01\nRECOVERY_UUID=5d2a-11f4\n\
+            ROOT_UUID=8b0c6f2a-7e1d-4b3a-9c5e-1f2d3a4b5c6d\nOEM_MODE=0\n";
+        let info = "Pop!_OS 22.04 LTS \"Jammy Jellyfish\" - Release amd64 (20230818)\n";
+        let recovery = fixture(&[
+            ("recovery.conf", conf),
+            (".disk/info", info),
+            ("casper-5d2a-11f4/filesystem.squashfs", ""),
+        ]);
+
+        let image = detect_recovery(recovery.path()).unwrap();
+        assert_eq!(image.name.as_deref(), Some("Pop!_OS"));
+        assert_eq!(image.version.as_deref(), Some("22.04"));
+        assert_eq!(image.config["RECOVERY_UUID"], "5d2a-11f4");
+        assert_eq!(image.config["ROOT_UUID"], "8b0c6f2a-7e1d-4b3a-9c5e-1f2d3a4b5c6d");
+        assert_eq!(image.config["EFI_UUID"], "PARTUUID=0b9d6c41-01");
+
+        let recovery = fixture(&[("recovery.conf", conf), ("casper/filesystem.squashfs", "")]);
+        let image = detect_recovery(recovery.path()).unwrap();
+        assert_eq!((image.name, image.version), (None, None));
+
+        assert_eq!(detect_recovery(fixture(&[("recovery.conf", conf)]).path()), None);
+        assert_eq!(detect_recovery(fixture(&[("casper/initrd.gz", "")]).path()), None);
+    }
+
+    #[test]
+    fn fstab_unsupported_sources() {
+        let fstab = "tmpfs /tmp tmpfs defaults 0 0\nproc /proc proc defaults 0 0\n";
+        let fstab = parse_fstab(Cursor::new(fstab));
+        assert!(fstab.entries.is_empty());
+    }
+
+    #[test]
+    fn fstab_boot_partition() {
+        let fstab = "UUID=b7e1 / ext4 defaults 0 1\n\
+            UUID=9c3d /boot ext4 defaults 0 2\n\
+            UUID=4A1F-22B0 /boot/efi vfat umask=0077 0 1\n";
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { boot, efi, .. })) => {
+                assert_eq!(boot, Some(PartitionID::new_uuid("9c3d".into())));
+                assert_eq!(efi, Some(PartitionID::new_uuid("4A1F-22B0".into())));
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+
+        let fstab = "UUID=b7e1 / ext4 defaults 0 1\nUUID=4A1F-22B0 /boot/efi vfat umask=0077 0 1\n";
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { boot, efi, .. })) => {
+                assert_eq!(boot, None);
+                assert!(efi.is_some());
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fstab_usr_partition() {
+        let usr = |fstab: &str| {
+            let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+            match detect_linux(dir.path()) {
+                Some(OS::Linux(LinuxInstall { usr, usr_separate, .. })) => (usr, usr_separate),
+                other => panic!("expected Linux, found {:?}", other),
+            }
+        };
+
+        let separate = "UUID=b7e1 / ext4 defaults 0 1\nUUID=51c0 /usr ext4 ro 0 2\n";
+        assert_eq!(usr(separate), (Some(PartitionID::new_uuid("51c0".into())), true));
+        assert_eq!(
+            usr("UUID=b7e1 / ext4 defaults 0 1\nUUID=0aa2 /usr/local ext4 0 2\n"),
+            (None, false)
+        );
+    }
+
+    #[test]
+    fn fstab_part_refs() {
+        let fstab = "UUID=b7e1 / ext4 defaults 0 1\n\
+                     PARTUUID=9f3a-01 /boot/efi vfat umask=0077 0 1\n\
+                     LABEL=data /data ext4 defaults 0 2\n";
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+        let mut os = detect_linux(dir.path()).unwrap();
+        let part_ref = |uuid: Option<&str>, partuuid: Option<&str>, label: Option<&str>| PartRef {
+            uuid: uuid.map(String::from),
+            partuuid: partuuid.map(String::from),
+            label: label.map(String::from),
+        };
+
+        match os {
+            OS::Linux(LinuxInstall { ref part_refs, .. }) => assert_eq!(
+                part_refs.values().cloned().collect::<Vec<_>>(),
+                [
+                    part_ref(Some("b7e1"), None, None),
+                    part_ref(None, Some("9f3a-01"), None),
+                    part_ref(None, None, Some("data")),
+                ]
+            ),
+            ref other => panic!("expected Linux, found {:?}", other),
+        }
+
+        // The data partition isn't attached, so only what the fstab says is known of it.
+        let resolve = |id: &PartitionID| match id.id.as_str() {
+            "b7e1" => Some(PathBuf::from("/dev/sda2")),
+            "9f3a-01" => Some(PathBuf::from("/dev/sda1")),
+            _ => None,
+        };
+        let identify = |path: &Path| {
+            let (uuid, part_uuid, label) = match path.to_str() {
+                Some("/dev/sda1") => ("4A2C-11F0", "9f3a-01", None),
+                _ => ("b7e1", "9f3a-02", Some("root")),
+            };
+            PartitionIdentifiers {
+                uuid: Some(uuid.into()),
+                part_uuid: Some(part_uuid.into()),
+                label: label.map(String::from),
+                ..PartitionIdentifiers::default()
+            }
+        };
+
+        complete_part_refs(&mut os, Path::new("/dev/sda2"), resolve, identify);
+        match os {
+            OS::Linux(LinuxInstall { part_refs, .. }) => {
+                let root = part_ref(Some("b7e1"), Some("9f3a-02"), Some("root"));
+                let efi = part_ref(Some("4A2C-11F0"), Some("9f3a-01"), None);
+                assert_eq!(part_refs[Path::new("/")], root);
+                assert_eq!(part_refs[Path::new("/boot/efi")], efi);
+                assert_eq!(part_refs[Path::new("/data")], part_ref(None, None, Some("data")));
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fstab_whitespace_and_comments() {
+        let fstab = "# <file system>\t<mount point>\t<type>\n\
+                     UUID=b7e1\t/\text4\terrors=remount-ro\t0\t1\n\
+                     \n\
+                     \t  \n\
+                     \t UUID=51c0  \t /home ext4 defaults 0 2\n\
+                     UUID=0aa2 /srv xfs # moved to the new disk\n\
+                     LABEL=My\\040Data /mnt/my\\040data ext4 defaults,nofail 0 2 # backups\n\
+                     # UUID=dead /old ext4 defaults 0 2\n";
+        let fstab = parse_fstab(Cursor::new(fstab));
+        let entries: Vec<_> = fstab
+            .entries
+            .iter()
+            .map(|entry| {
+                let target = entry.target.to_str().unwrap();
+                (entry.source.id.as_str(), target, entry.fs.as_str(), entry.options.as_str())
+            })
+            .collect();
+
+        assert_eq!(
+            entries,
+            [
+                ("b7e1", "/", "ext4", "errors=remount-ro"),
+                ("51c0", "/home", "ext4", "defaults"),
+                ("0aa2", "/srv", "xfs", "defaults"),
+                ("My Data", "/mnt/my data", "ext4", "defaults,nofail"),
+            ]
+        );
+
+        assert_eq!(unescape_fstab_field("a\\011b\\134c"), "a\tb\\c");
+        assert_eq!(unescape_fstab_field("trailing\\04"), "trailing\\04");
+        assert_eq!(unescape_fstab_field("\\x20"), "\\x20");
+    }
+
+    #[test]
+    fn fstab_truncated_lines() {
+        let fstab = parse_fstab(Cursor::new("UUID=b7e1\n/dev/sda2\n   \n"));
+        assert!(fstab.entries.is_empty());
+
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", "UUID=b7e1\n")]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { home, efi, swap, .. })) => {
+                assert_eq!((home, efi), (None, None));
+                assert!(swap.is_empty());
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fstab_lvm_volumes() {
+        let fstab = "/dev/mapper/vg0-root / ext4 defaults 0 1\n\
+            /dev/mapper/my--vg-home--lv /home ext4 defaults 0 2\n\
+            /dev/vg0/var /var xfs defaults 0 2\n\
+            /dev/disk/by-uuid/4a1f-22b0 /boot/efi vfat umask=0077 0 1\n\
+            /dev/sda3 /recovery vfat defaults 0 0\n";
+        let fstab = parse_fstab(Cursor::new(fstab));
+        assert_eq!(fstab.mount_source("/"), Some(MountSource::LvmVolume("vg0/root".into())));
+        assert_eq!(
+            fstab.mount_source("/home"),
+            Some(MountSource::LvmVolume("my-vg/home-lv".into()))
+        );
+        assert_eq!(fstab.mount_source("/var"), Some(MountSource::LvmVolume("vg0/var".into())));
+        assert!(matches!(fstab.mount_source("/boot/efi"), Some(MountSource::Partition { .. })));
+        assert!(matches!(fstab.mount_source("/recovery"), Some(MountSource::Partition { .. })));
+
+        // Without a crypttab entry to resolve them, these are left as they are.
+        let fstab = "/dev/mapper/luks-0c5a1b2f-7d3e-4f0a-9b1c-2e8d6a4f3b10 / ext4 defaults 0 1\n\
+            /dev/mapper/home-crypt /home ext4 defaults 0 2\n\
+            /dev/mapper/mpatha-part1 /srv xfs defaults 0 2\n";
+        let fstab = parse_fstab(Cursor::new(fstab));
+        for &target in &["/", "/home", "/srv"] {
+            match fstab.mount_source(target) {
+                Some(MountSource::Partition { id, encrypted: false }) => {
+                    assert!(id.id.starts_with("/dev/mapper/"))
+                }
+                other => panic!("expected a partition at {}, found {:?}", target, other),
+            }
+        }
+
+        let fstab = "/dev/mapper/vg0-root / ext4 defaults 0 1\n/dev/mapper/vg0-home /home ext4 defaults 0 2\n";
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+        match detect_linux(dir.path()) {
+            Some(OS::Linux(LinuxInstall { home, .. })) => {
+                assert_eq!(home, Some(MountSource::LvmVolume("vg0/home".into())))
+            }
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fstab_network_home() {
+        let nfs = "UUID=b7e1 / ext4 defaults 0 1\nserver:/export/home /home nfs rw,hard 0 0\n";
+        let cifs =
+            "UUID=b7e1 / ext4 defaults 0 1\n//server/home /home cifs credentials=/etc/smb 0 0\n";
+        let tmpfs = "UUID=b7e1 / ext4 defaults 0 1\n/dev/shm /home tmpfs size=1G 0 0\n";
+        for fstab in &[nfs, cifs, tmpfs] {
+            let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", fstab)]);
+            match detect_linux(dir.path()) {
+                Some(OS::Linux(LinuxInstall { home, .. })) => assert_eq!(home, None, "{}", fstab),
+                other => panic!("expected Linux, found {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn windows_registry_version() {
+        let dir = fixture(&[("Windows/System32/ntoskrnl.exe", "")]);
+        let hive = registry::tests::software_hive(&[
+            ("ProductName", "Windows 10 Pro"),
+            ("DisplayVersion", "22H2"),
+            ("CurrentBuild", "19045"),
+            ("EditionID", "Professional"),
+        ]);
+        fs::create_dir_all(dir.path().join("Windows/System32/config")).unwrap();
+        fs::write(dir.path().join("Windows/System32/config/SOFTWARE"), hive).unwrap();
+
+        let os = detect_windows(dir.path()).unwrap();
+        assert_eq!(os.to_string(), "Windows 10 Pro 22H2 (19045)");
+        assert_eq!(
+            os,
+            OS::Windows {
+                product_name: "Windows 10 Pro".into(),
+                version: Some("22H2".into()),
+                build: Some(19045),
+                edition: Some("Professional".into()),
+                arch: Some(Arch::X86),
+                wsl: Vec::new(),
+                hibernated: false,
+                pagefile: false,
+                boot_manager: false,
+            }
+        );
+    }
+
+    #[test]
+    fn windows_11_registry_version() {
+        let dir = fixture(&[("Windows/System32/ntoskrnl.exe", "")]);
+        let hive = registry::tests::software_hive(&[
+            ("ProductName", "Windows 10 Pro"),
+            ("DisplayVersion", "23H2"),
+            ("CurrentBuild", "22631"),
+            ("EditionID", "Professional"),
+        ]);
+        fs::create_dir_all(dir.path().join("Windows/System32/config")).unwrap();
+        fs::write(dir.path().join("Windows/System32/config/SOFTWARE"), hive).unwrap();
+
+        let os = detect_windows(dir.path()).unwrap();
+        assert_eq!(os.to_string(), "Windows 11 Pro 23H2 (22631)");
+        assert_eq!(os.name(), "Windows 11 Pro");
+    }
+
+    #[test]
+    fn windows_hibernated() {
+        let hibernated = |files: &[(&str, &str)]| match detect_bare_windows(files) {
+            Some(OS::Windows { hibernated, .. }) => hibernated,
+            other => panic!("expected Windows, found {:?}", other),
+        };
+
+        assert!(hibernated(&[("hiberfil.sys", "HIBR")]));
+        assert!(hibernated(&[("HIBERFIL.SYS", "HIBR")]));
+        assert!(!hibernated(&[("hiberfil.sys", "")]));
+        assert!(!hibernated(&[]));
+    }
+
+    #[test]
+    fn windows_pagefile() {
+        let pagefile = |files: &[(&str, &str)]| match detect_bare_windows(files) {
+            Some(OS::Windows { pagefile, .. }) => pagefile,
+            other => panic!("expected Windows, found {:?}", other),
+        };
+
+        assert!(pagefile(&[("pagefile.sys", "")]));
+        assert!(pagefile(&[("PAGEFILE.SYS", "")]));
+        assert!(!pagefile(&[("swapfile.sys", "")]));
+        assert!(!pagefile(&[]));
+    }
+
+    #[test]
+    fn windows_wsl_distros() {
+        let state = |package: &str, file: &str| {
+            format!("Users/alice/AppData/Local/Packages/{}/LocalState/{}", package, file)
+        };
+        let debian = "PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\nNAME=\"Debian GNU/Linux\"\n";
+        let dir = fixture(&[
+            ("Windows/System32/ntoskrnl.exe", ""),
+            (&state("CanonicalGroupLimited.Ubuntu22.04LTS_79rhkp1fndgsc", "ext4.vhdx"), ""),
+            (
+                &state("TheDebianProject.DebianGNULinux_76v4gfsz19hv4", "rootfs/etc/os-release"),
+                debian,
+            ),
+            (&state("Microsoft.WindowsCalculator_8wekyb3d8bbwe", "settings.dat"), ""),
+        ]);
+
+        match detect_windows(dir.path()) {
+            Some(OS::Windows { wsl, .. }) => {
+                assert_eq!(wsl, ["Debian GNU/Linux 12 (bookworm)", "Ubuntu22.04LTS"]);
+            }
+            other => panic!("expected Windows, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn windows_arch() {
+        let dir = fixture(&[("Windows/System32/ntoskrnl.exe", "")]);
+        match detect_windows(dir.path()) {
+            Some(OS::Windows { arch, .. }) => assert_eq!(arch, Some(Arch::X86)),
+            other => panic!("expected Windows, found {:?}", other),
+        }
+
+        let dir =
+            fixture(&[("Windows/System32/ntoskrnl.exe", ""), ("Windows/SysWOW64/ntdll.dll", "")]);
+        match detect_windows(dir.path()) {
+            Some(OS::Windows { arch, .. }) => assert_eq!(arch, Some(Arch::X86_64)),
+            other => panic!("expected Windows, found {:?}", other),
+        }
+
+        // ARM64 installs have a `SysWOW64` as well, for x86 emulation.
+        let mut kernel = pe::tests::image(&pe::tests::version_resource());
+        kernel[0x44..0x46].copy_from_slice(&0xAA64u16.to_le_bytes());
+        fs::write(dir.path().join("Windows/System32/ntoskrnl.exe"), kernel).unwrap();
+        match detect_windows(dir.path()) {
+            Some(OS::Windows { arch, .. }) => assert_eq!(arch, Some(Arch::Aarch64)),
+            other => panic!("expected Windows, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn windows_lowercase_dirs() {
+        let dir =
+            fixture(&[("windows/system32/NTOSKRNL.EXE", ""), ("windows/syswow64/ntdll.dll", "")]);
+        match detect_windows(dir.path()) {
+            Some(OS::Windows { product_name, arch, .. }) => {
+                assert_eq!(product_name, "Windows");
+                assert_eq!(arch, Some(Arch::X86_64));
+            }
+            other => panic!("expected Windows, found {:?}", other),
+        }
+
+        let dir = fixture(&[("WINDOWS/System32/drivers/null.sys", "")]);
+        assert_eq!(detect_windows(dir.path()), None);
+    }
+
+    #[test]
+    fn windows_version_fallback() {
+        let dir = fixture(&[
+            ("Windows/System32/ntoskrnl.exe", ""),
+            ("Windows/System32/config/SOFTWARE", "regf, but not really"),
+        ]);
+
+        match detect_windows(dir.path()) {
+            Some(OS::Windows { product_name, build, .. }) => {
+                assert_eq!(product_name, "Windows");
+                assert_eq!(build, None);
+            }
+            other => panic!("expected Windows, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn windows_kernel_version() {
+        let dir = fixture(&[("Windows/System32/config/SOFTWARE", "regf, but not really")]);
+        let kernel = pe::tests::image(&pe::tests::version_resource());
+        fs::write(dir.path().join("Windows/System32/ntoskrnl.exe"), kernel).unwrap();
+
+        match detect_windows(dir.path()) {
+            Some(OS::Windows { product_name, version, build, .. }) => {
+                assert_eq!(product_name, "Windows");
+                assert_eq!(version, None);
+                assert_eq!(build, Some(19041));
+            }
+            other => panic!("expected Windows, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn btrfs_subvolume_list() {
+        let output = "ID 256 gen 4123 top level 5 path @\n\
+            ID 257 gen 4120 top level 5 path @home\n\
+            ID 258 gen 4098 top level 5 path fedora root\n\
+            ID 259 gen 3901 top level 256 path var/lib/machines\n\
+            ID 260 gen 3870 top level 257 path @home/.snapshots/1/snapshot\n\
+            ID 261 gen 3012 top level 5 path @snapshots/2023-10-01\n\
+            not a subvolume\n";
+
+        assert_eq!(
+            parse_subvolume_list(output),
+            ["@", "@home", "fedora root", "@snapshots/2023-10-01"]
+        );
+        assert!(parse_subvolume_list("").is_empty());
+    }
+
+    #[test]
+    fn detailed_linux_evidence() {
+        let dir = fixture(&[
+            ("etc/os-release", POP_OS_RELEASE),
+            ("etc/fstab", FSTAB),
+            ("usr/lib/systemd/systemd", ""),
+            ("boot/vmlinuz-6.2.0-39-generic", ""),
+        ]);
+
+        let detection = detect_detailed(dir.path()).unwrap();
+        assert_eq!(detection.os, detect_linux(dir.path()).unwrap());
+        assert_eq!(detection.confidence, Confidence::Full);
+        assert_eq!(
+            detection.evidence,
+            ["etc/os-release", "etc/fstab", "usr/lib/systemd/systemd", "ID=ubuntu"]
+        );
+
+        let dir = fixture(&[("usr/lib/os-release", POP_OS_RELEASE)]);
+        let detection = detect_detailed(dir.path()).unwrap();
+        assert_eq!(detection.confidence, Confidence::Container);
+        assert_eq!(detection.evidence, ["usr/lib/os-release", "ID=ubuntu"]);
+    }
+
+    #[test]
+    fn detailed_windows_evidence() {
+        let dir =
+            fixture(&[("windows/System32/ntoskrnl.exe", ""), ("windows/SysWOW64/ntdll.dll", "")]);
+        let hive = registry::tests::software_hive(&[
+            ("ProductName", "Windows 10 Pro"),
+            ("CurrentBuild", "19045"),
+        ]);
+        fs::create_dir(dir.path().join("windows/System32/config")).unwrap();
+        fs::write(dir.path().join("windows/System32/config/SOFTWARE"), hive).unwrap();
+
+        let detection = detect_detailed(dir.path()).unwrap();
+        assert_eq!(detection.confidence, Confidence::Full);
+        assert_eq!(
+            detection.evidence,
+            [
+                "windows/System32/ntoskrnl.exe",
+                "windows/System32/config/SOFTWARE",
+                "windows/SysWOW64",
+                "ProductName=Windows 10 Pro",
+                "CurrentBuild=19045",
+            ]
+        );
+
+        let dir = fixture(&[("bootmgr", ""), ("Boot/BCD", "")]);
+        let detection = detect_detailed(dir.path()).unwrap();
+        assert_eq!(detection.confidence, Confidence::BootOnly);
+        assert_eq!(detection.evidence, ["bootmgr", "Boot/BCD"]);
+    }
+
+    #[test]
+    fn partial_signatures() {
+        let hints = |files: &[(&str, &str)]| match detect_os_from_path(fixture(files).path()) {
+            Some(OS::Unknown { hints }) => hints,
+            other => panic!("expected an unknown OS, found {:?}", other),
+        };
+
+        assert_eq!(hints(&[("etc/hostname", "stray\n")]), ["etc/"]);
+        assert_eq!(hints(&[("Boot/BCD", "")]), ["Boot/"]);
+        assert_eq!(
+            hints(&[("bootmgr", ""), ("Windows/explorer.exe", "")]),
+            ["Windows/", "bootmgr"]
+        );
+
+        let unknown = OS::Unknown { hints: vec!["etc/".into(), "usr/".into()] };
+        assert_eq!(unknown.to_string(), "Unknown OS (etc/, usr/)");
+        assert_eq!((unknown.name(), unknown.version()), ("Unknown", None));
+
+        // Full detections take precedence, and data partitions have no hints at all.
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE), ("boot/vmlinuz", "")]);
+        assert!(matches!(detect_os_from_path(dir.path()), Some(OS::Linux(LinuxInstall { .. }))));
+        assert_eq!(detect_os_from_path(fixture(&[("Documents/notes.txt", "")]).path()), None);
+        assert_eq!(detect_all(fixture(&[("etc/hostname", "")]).path()), []);
+    }
+
+    #[test]
+    fn summaries_match_detection() {
+        let unnamed = "VERSION_ID=\"1.0\"\nPRETTY_NAME='Homebrew Linux 1.0'\n";
+        let trees: &[&[(&str, &str)]] = &[
+            &[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", FSTAB)],
+            &[("usr/lib/os-release", POP_2204_OS_RELEASE)],
+            &[("etc/os-release", unnamed)],
+            &[("etc/os-release", "ID=fedora\nNAME=Fedora\n"), ("etc/qubes/guid.conf", "")],
+            &[("Windows/System32/ntoskrnl.exe", ""), ("etc/os-release", POP_OS_RELEASE)],
+            &[("Windows/System32/ntoskrnl.exe", "")],
+            &[("etc/hostname", "damaged\n")],
+            // A corrupt os-release is no more summarized than it's detected.
+            &[("etc/os-release", "\u{7f}ELF garbage\n")],
+            &[("etc/os-release", "\u{7f}ELF garbage\n"), ("Windows/System32/ntoskrnl.exe", "")],
+        ];
+
+        for files in trees {
+            let dir = fixture(files);
+            let full = detect_os_from_path(dir.path());
+            let summary = detect_os_summary(dir.path());
+            assert_eq!(summary, full.as_ref().and_then(OsSummary::of), "for {:?}", files);
+        }
+
+        let dir = fixture(&[("etc/os-release", unnamed)]);
+        match detect_os_summary(dir.path()) {
+            Some(OsSummary { kind: OsKind::Linux, name, version }) => {
+                assert_eq!(name, "Homebrew Linux 1.0");
+                assert_eq!(version.as_deref(), Some("1.0"));
+            }
+            other => panic!("expected a Linux summary, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn detect_all_signatures() {
+        let dir =
+            fixture(&[("Windows/System32/ntoskrnl.exe", ""), ("etc/os-release", POP_OS_RELEASE)]);
+
+        let found = detect_all(dir.path());
+        assert_eq!(found.len(), 2);
+        match (&found[0], &found[1]) {
+            (&OS::Linux(LinuxInstall { .. }), &OS::Windows { .. }) => (),
+            other => panic!("expected Linux and Windows, found {:?}", other),
+        }
+
+        match detect_os_from_path(dir.path()) {
+            Some(OS::Linux(LinuxInstall { .. })) => (),
+            other => panic!("expected Linux, found {:?}", other),
+        }
+    }
+
+    const CHROMEOS_LSB_RELEASE: &str = "CHROMEOS_AUSERVER=https://tools.google.com/service/update2
+CHROMEOS_BOARD_APPID={DD3A4F2D-2A03-4A2E-A6D1-5C1E5A1B6A3F}
+CHROMEOS_RELEASE_BOARD=reven-signed-mp-v2keys
+CHROMEOS_RELEASE_BUILD_TYPE=Official Build
+CHROMEOS_RELEASE_CHROME_MILESTONE=114
+CHROMEOS_RELEASE_DESCRIPTION=15437.61.0 (Official Build) stable-channel reven
+CHROMEOS_RELEASE_NAME=Chrome OS
+CHROMEOS_RELEASE_TRACK=stable-channel
+CHROMEOS_RELEASE_VERSION=15437.61.0
+DEVICETYPE=CHROMEBOOK
+";
+
+    #[test]
+    fn chromeos_detection() {
+        let dir = fixture(&[("etc/lsb-release", CHROMEOS_LSB_RELEASE)]);
+        assert_eq!(
+            detect_os_from_path(dir.path()),
+            Some(OS::ChromeOs("Chrome OS 15437.61.0 (Official Build) stable-channel reven".into()))
+        );
+
+        let dir = fixture(&[(
+            "etc/lsb-release",
+            "# ChromiumOS\nCHROMEOS_RELEASE_NAME=\"Chromium OS\"\nCHROMEOS_RELEASE_VERSION=15437.0.0\n",
+        )]);
+        assert_eq!(detect_chromeos(dir.path()), Some(OS::ChromeOs("Chromium OS 15437.0.0".into())));
+
+        // Other distributions also ship a lsb-release, without the ChromeOS keys.
+        let dir = fixture(&[("etc/lsb-release", "DISTRIB_ID=Ubuntu\nDISTRIB_RELEASE=22.04\n")]);
+        assert_eq!(detect_chromeos(dir.path()), None);
+    }
+
+    const ANDROID_BUILD_PROP: &str = "
+# begin build properties
+# autogenerated by buildinfo.sh
+ro.build.id=PI
+ro.build.display.id=android_x86_64-userdebug 9 PI eng.cwhuan.20200406.173108 test-keys
+ro.build.version.sdk=28
+ro.build.version.release=9
+ro.product.model=Android-x86
+ro.product.brand=Android-x86
+# end build properties
+
+ro.com.android.dateformat=MM-dd-yyyy
+";
+
+    #[test]
+    fn android_detection() {
+        let dir = fixture(&[("system/build.prop", ANDROID_BUILD_PROP)]);
+        assert_eq!(
+            detect_os_from_path(dir.path()),
+            Some(OS::Android("Android 9 (Android-x86)".into()))
+        );
+
+        let dir = fixture(&[("build.prop", "ro.build.version.release=11\n")]);
+        assert_eq!(detect_android(dir.path()), Some(OS::Android("Android 11".into())));
+
+        let dir =
+            fixture(&[("system/build.prop", "# ro.build.version.release=9\nro.product.model=x\n")]);
+        assert_eq!(detect_android(dir.path()), None);
+    }
+
+    const FREEBSD_VERSION: &str = r#"#!/bin/sh
+#-
+# Copyright (c) 2013 Dag-Erling Smørgrav
+# All rights reserved.
+
+set -e
+
+USERLAND_VERSION="13.2-RELEASE-p4"
+
+: ${ROOT:=}
+: ${LOADER_DIR:=$ROOT/boot}
+"#;
+
+    #[test]
+    fn bsd_detection() {
+        let dir = fixture(&[
+            ("etc/rc.conf", "hostname=\"beastie\"\nsshd_enable=\"YES\"\n"),
+            ("bin/freebsd-version", FREEBSD_VERSION),
+            ("etc/os-release", "NAME=FreeBSD\nVERSION_ID=\"13.2\"\nID=freebsd\n"),
+        ]);
+        let freebsd = OS::Bsd { flavor: "FreeBSD".into(), version: Some("13.2-RELEASE-p4".into()) };
+        assert_eq!(detect_os_from_path(dir.path()), Some(freebsd));
+
+        let dir = fixture(&[
+            ("etc/rc.conf", ""),
+            ("bin/freebsd-version", "#!/bin/sh\n"),
+            ("etc/os-release", "NAME=FreeBSD\nVERSION_ID=\"13.2\"\nID=freebsd\n"),
+        ]);
+        let freebsd = OS::Bsd { flavor: "FreeBSD".into(), version: Some("13.2".into()) };
+        assert_eq!(detect_bsd(dir.path()), Some(freebsd));
+
+        let dir = fixture(&[("etc/rc.conf", ""), ("bsd", "")]);
+        assert_eq!(
+            detect_bsd(dir.path()),
+            Some(OS::Bsd { flavor: "OpenBSD".into(), version: None })
+        );
+
+        let dir = fixture(&[("etc/rc.conf", ""), ("netbsd", "")]);
+        assert_eq!(
+            detect_bsd(dir.path()),
+            Some(OS::Bsd { flavor: "NetBSD".into(), version: None })
+        );
+
+        // Some Linux distributions carry an rc.conf of their own.
+        let dir = fixture(&[("etc/rc.conf", ""), ("etc/os-release", POP_OS_RELEASE)]);
+        assert_eq!(detect_bsd(dir.path()), None);
+    }
+
+    #[test]
+    fn os_display() {
+        let os = WindowsVersion { build: Some(19045), ..WindowsVersion::named("Windows 10 Pro") };
+        assert_eq!(os.into_os(Some(Arch::X86_64)).to_string(), "Windows 10 Pro (19045)");
+        assert_eq!(WindowsVersion::named("Windows").into_os(None).to_string(), "Windows");
+        assert_eq!(snow_leopard().to_string(), "Mac OS X (10.6.2) [10C540]");
+        assert_eq!(OS::ChromeOs("Chrome OS 15437.61.0".into()).to_string(), "Chrome OS 15437.61.0");
+        assert_eq!(OS::Android("Android 9".into()).to_string(), "Android 9");
+        let freebsd = OS::Bsd { flavor: "FreeBSD".into(), version: Some("13.2-RELEASE".into()) };
+        assert_eq!(freebsd.to_string(), "FreeBSD 13.2-RELEASE");
+        assert_eq!(OS::Bsd { flavor: "NetBSD".into(), version: None }.to_string(), "NetBSD");
+
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        assert_eq!(detect_linux(dir.path()).unwrap().to_string(), "Pop!_OS 18.04 LTS");
+
+        let dir = fixture(&[("etc/os-release", "NAME=\"Arch Linux\"\nID=arch\n")]);
+        assert_eq!(detect_linux(dir.path()).unwrap().to_string(), "Arch Linux");
+    }
+
+    #[test]
+    fn os_name_and_version() {
+        let windows = WindowsVersion {
+            version: Some("22H2".into()),
+            build: Some(19045),
+            ..WindowsVersion::named("Windows 10 Pro")
+        };
+        let windows = windows.into_os(Some(Arch::X86_64));
+        assert_eq!((windows.name(), windows.version()), ("Windows 10 Pro", Some("22H2".into())));
+
+        let windows = WindowsVersion { build: Some(7601), ..WindowsVersion::named("Windows 7") };
+        let windows = windows.into_os(None);
+        assert_eq!((windows.name(), windows.version()), ("Windows 7", Some("7601".into())));
+
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        let linux = detect_linux(dir.path()).unwrap();
+        assert_eq!((linux.name(), linux.version()), ("Pop!_OS", Some("18.04".into())));
+
+        let dir =
+            fixture(&[("etc/os-release", "NAME=\"Arch Linux\"\nID=arch\nBUILD_ID=rolling\n")]);
+        let linux = detect_linux(dir.path()).unwrap();
+        assert_eq!((linux.name(), linux.version()), ("Arch Linux", None));
+
+        let dir = fixture(&[("etc/os-release", "ID=unknown\n")]);
+        assert_eq!(detect_linux(dir.path()).unwrap().name(), "Linux");
+
+        let mac = snow_leopard();
+        assert_eq!((mac.name(), mac.version()), ("Mac OS X", Some("10.6.2".into())));
+
+        let android = OS::Android("Android 9 (Android-x86)".into());
+        assert_eq!((android.name(), android.version()), ("Android", Some("9".into())));
+
+        let chromeos = OS::ChromeOs("Chrome OS 15437.61.0 (Official Build)".into());
+        assert_eq!(chromeos.name(), "Chrome OS 15437.61.0 (Official Build)");
+        assert_eq!(chromeos.version(), None);
+
+        let bsd = OS::Bsd { flavor: "FreeBSD".into(), version: Some("13.2-RELEASE".into()) };
+        assert_eq!((bsd.name(), bsd.version()), ("FreeBSD", Some("13.2-RELEASE".into())));
+    }
+
+    #[test]
+    fn os_equality() {
+        let first = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", FSTAB)]);
+        let second = fixture(&[("etc/os-release", POP_OS_RELEASE), ("etc/fstab", FSTAB)]);
+        assert_eq!(detect_linux(first.path()), detect_linux(second.path()));
+
+        let other = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        assert_ne!(detect_linux(first.path()), detect_linux(other.path()));
+        assert_ne!(WindowsVersion::named("Windows").into_os(None), OS::ChromeOs("Windows".into()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let fstab = "UUID=b7e1 / btrfs subvol=@ 0 0\nUUID=b7e1 /home btrfs subvol=@home 0 0\n";
+        let dir = fixture(&[
+            ("etc/os-release", POP_OS_RELEASE),
+            ("etc/fstab", &format!("{}{}", FSTAB, fstab)),
+        ]);
+
+        let detected = vec![
+            detect_linux(dir.path()).unwrap(),
+            WindowsVersion { build: Some(19045), ..WindowsVersion::named("Windows 10 Pro") }
+                .into_os(Some(Arch::X86_64)),
+            snow_leopard(),
+        ];
+
+        for os in detected {
+            let json = serde_json::to_string(&os).unwrap();
+            assert_eq!(serde_json::from_str::<OS>(&json).unwrap(), os);
+        }
+    }
+
+    #[test]
+    fn windows_boot_manager() {
+        let dir = fixture(&[("bootmgr", ""), ("Boot/BCD", "")]);
+        match detect_windows(dir.path()) {
+            Some(os @ OS::Windows { boot_manager: true, arch: None, .. }) => {
+                assert_eq!(os.name(), "Windows Boot Manager");
+                assert_eq!(confidence(&os), Confidence::BootOnly);
+            }
+            other => panic!("expected the Windows Boot Manager, found {:?}", other),
+        }
+
+        // An install of unknown architecture is still a full install.
+        let os = WindowsVersion::named("Windows").into_os(None);
+        assert_eq!(confidence(&os), Confidence::Full);
+
+        let dir =
+            fixture(&[("bootmgr", ""), ("Boot/BCD", ""), ("Windows/System32/ntoskrnl.exe", "")]);
+        let os = WindowsVersion::named("Windows").into_os(Some(Arch::X86));
+        assert_eq!(detect_windows(dir.path()), Some(os));
+
+        let dir = fixture(&[("bootmgr", "")]);
+        assert_eq!(detect_windows(dir.path()), None);
+    }
+
+    #[test]
+    fn macos_detection() {
+        let dir = fixture(&[("System/Library/CoreServices/SystemVersion.plist", MAC_PLIST)]);
+        assert_eq!(detect_macos(dir.path()), Some(snow_leopard()));
+    }
+
+    #[test]
+    fn macos_server_detection() {
+        let server = MAC_PLIST.replace("Mac OS X", "Mac OS X Server");
+        let dir = fixture(&[("System/Library/CoreServices/ServerVersion.plist", &server)]);
+        match detect_macos(dir.path()) {
+            Some(os) => assert_eq!(os.to_string(), "Server: Mac OS X Server (10.6.2) [10C540]"),
+            other => panic!("expected macOS Server, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn macos_system_plist_preferred_over_server() {
+        let server = MAC_PLIST.replace("Mac OS X", "Mac OS X Server");
+        let dir = fixture(&[
+            ("System/Library/CoreServices/SystemVersion.plist", MAC_PLIST),
+            ("System/Library/CoreServices/ServerVersion.plist", &server),
+        ]);
+        assert_eq!(detect_macos(dir.path()), Some(snow_leopard()));
+    }
+
+    #[test]
+    fn macos_data_volume() {
+        let dir = fixture(&[
+            ("Users/alice/Documents/notes.txt", ""),
+            ("private/var/db/dslocal/nodes/Default/users/alice.plist", ""),
+            ("Library/Preferences/.GlobalPreferences.plist", ""),
+        ]);
+
+        let os = detect_macos(dir.path()).unwrap();
+        assert_eq!(os.to_string(), "macOS (Data volume)");
+        assert_eq!((os.name(), os.version()), ("macOS (Data volume)", None));
+        assert_eq!(detect_os_from_path(dir.path()), Some(os));
+
+        let dir = fixture(&[("Users/alice/Documents/notes.txt", "")]);
+        assert_eq!(detect_macos(dir.path()), None);
+    }
+
+    #[test]
+    fn macos_not_detected_on_linux() {
+        let dir = fixture(&[("etc/os-release", POP_OS_RELEASE)]);
+        assert!(detect_macos(dir.path()).is_none());
+        match detect_os_from_path(dir.path()) {
+            Some(OS::Linux(LinuxInstall { .. })) => (),
+            other => panic!("expected Linux, found {:?}", other),
+        }
     }
 }
@@ -0,0 +1,335 @@
+//! Reads the partitions of a whole-disk image from its GPT, or failing that, its MBR.
+//!
+//! Only what's needed to find each partition is read: neither the GPT's checksums nor its
+//! backup header are validated.
+
+use gpt::{classify_by_gpt_type, ExpectedOs};
+use std::io::{self, Read, Seek, SeekFrom};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+/// GPT headers are found in the second logical block, whose size depends on the disk.
+const SECTOR_SIZES: &[u64] = &[512, 4096];
+const MBR_SECTOR: u64 = 512;
+const MBR_ENTRIES: usize = 0x1BE;
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+/// The MBR partition type of a protective MBR, which covers a GPT disk.
+const MBR_PROTECTIVE: u8 = 0xEE;
+/// The MBR partition types of extended partitions, which hold logical partitions.
+const MBR_EXTENDED: &[u8] = &[0x05, 0x0F, 0x85];
+/// Logical partitions are numbered after the four primary partitions.
+const FIRST_LOGICAL: u32 = 5;
+/// A bound on the chain of logical partitions, in case it loops.
+const MAX_LOGICAL: u32 = 128;
+/// GPT entries are a multiple of 128 bytes, and no larger than a 4K sector.
+const GPT_ENTRY_ALIGN: usize = 128;
+const MAX_GPT_ENTRY: usize = 4096;
+
+/// A partition of a disk, found in its partition table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PartitionEntry {
+    /// The number of the partition, from 1, as in `/dev/sda1`.
+    pub number: u32,
+    /// Where the partition starts, in bytes from the start of the disk.
+    pub offset: u64,
+    /// The length of the partition, in bytes.
+    pub size: u64,
+    /// The type that the partition table records for the partition.
+    pub partition_type: PartitionType,
+    /// The name of a GPT partition, if it has one.
+    pub name: Option<String>,
+}
+
+/// The type of a partition, as recorded in the partition table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PartitionType {
+    /// A GPT partition type GUID, in upper case.
+    Gpt(String),
+    /// An MBR partition type, such as `0x83` for Linux.
+    Mbr(u8),
+}
+
+impl PartitionEntry {
+    /// What the partition is expected to hold, according to its type.
+    pub fn expected_os(&self) -> Option<ExpectedOs> {
+        match self.partition_type {
+            PartitionType::Gpt(ref guid) => classify_by_gpt_type(guid),
+            PartitionType::Mbr(0x07) | PartitionType::Mbr(0x27) => Some(ExpectedOs::Windows),
+            PartitionType::Mbr(0x82) => Some(ExpectedOs::Swap),
+            PartitionType::Mbr(0x83) | PartitionType::Mbr(0x8E) => Some(ExpectedOs::Linux),
+            PartitionType::Mbr(0xA5) => Some(ExpectedOs::FreeBsd),
+            PartitionType::Mbr(0xAF) => Some(ExpectedOs::MacOs),
+            PartitionType::Mbr(0xEF) => Some(ExpectedOs::EfiSystem),
+            PartitionType::Mbr(_) => None,
+        }
+    }
+
+    /// Whether the partition could hold an OS, rather than only swap or bootloaders.
+    pub fn may_hold_os(&self) -> bool {
+        !matches!(self.expected_os(), Some(ExpectedOs::Swap) | Some(ExpectedOs::EfiSystem))
+    }
+}
+
+/// Reads the partition table at the start of a disk image.
+///
+/// Disks without a GPT or an MBR have no partitions, as with an image of a single
+/// filesystem.
+pub fn read<R: Read + Seek>(disk: &mut R) -> io::Result<Vec<PartitionEntry>> {
+    for &sector_size in SECTOR_SIZES {
+        let header = read_at(disk, sector_size, 92)?;
+        if header.starts_with(GPT_SIGNATURE) {
+            return read_gpt(disk, &header, sector_size);
+        }
+    }
+
+    let mbr = read_at(disk, 0, MBR_SECTOR as usize)?;
+    if mbr.len() < MBR_SECTOR as usize || mbr[510..] != MBR_SIGNATURE {
+        return Ok(Vec::new());
+    }
+
+    read_mbr(disk, &mbr)
+}
+
+fn read_gpt<R: Read + Seek>(
+    disk: &mut R,
+    header: &[u8],
+    sector_size: u64,
+) -> io::Result<Vec<PartitionEntry>> {
+    if header.len() < 92 {
+        return Err(invalid("truncated GPT header"));
+    }
+
+    let entries_lba = u64_at(header, 72);
+    let count = u32_at(header, 80);
+    let entry_size = u32_at(header, 84) as usize;
+    // `is_multiple_of` would need Rust 1.87.
+    #[allow(clippy::manual_is_multiple_of)]
+    let plausible_size = (GPT_ENTRY_ALIGN..=MAX_GPT_ENTRY).contains(&entry_size)
+        && entry_size % GPT_ENTRY_ALIGN == 0;
+    if !plausible_size || count > 1024 {
+        return Err(invalid("implausible GPT partition entries"));
+    }
+
+    let entries_offset = entries_lba
+        .checked_mul(sector_size)
+        .ok_or_else(|| invalid("GPT partition entries are beyond the end of the disk"))?;
+    let entries = read_at(disk, entries_offset, count as usize * entry_size)?;
+    entries
+        .chunks_exact(entry_size)
+        .zip(1..)
+        .filter(|&(entry, _)| entry[..16].iter().any(|&byte| byte != 0))
+        .map(|(entry, number)| {
+            let (first, last) = (u64_at(entry, 32), u64_at(entry, 40));
+            let name: Vec<u16> = entry[56..128]
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .take_while(|&unit| unit != 0)
+                .collect();
+
+            let offset = first.checked_mul(sector_size);
+            let size = last
+                .checked_add(1)
+                .map(|end| end.saturating_sub(first))
+                .and_then(|sectors| sectors.checked_mul(sector_size));
+            match (offset, size) {
+                (Some(offset), Some(size)) => Ok(PartitionEntry {
+                    number,
+                    offset,
+                    size,
+                    partition_type: PartitionType::Gpt(format_guid(&entry[..16])),
+                    name: Some(String::from_utf16_lossy(&name)).filter(|name| !name.is_empty()),
+                }),
+                _ => Err(invalid("GPT partition is beyond the end of the disk")),
+            }
+        })
+        .collect()
+}
+
+fn read_mbr<R: Read + Seek>(disk: &mut R, mbr: &[u8]) -> io::Result<Vec<PartitionEntry>> {
+    let mut partitions = Vec::new();
+    for (entry, number) in mbr_entries(mbr).zip(1..) {
+        let (kind, start, sectors) = entry;
+        if kind == MBR_PROTECTIVE {
+            return Err(invalid("protective MBR without a GPT"));
+        } else if MBR_EXTENDED.contains(&kind) {
+            read_logical(disk, start, &mut partitions)?;
+        } else if kind != 0 {
+            partitions.push(mbr_partition(number, kind, start, sectors));
+        }
+    }
+
+    partitions.sort_by_key(|partition| partition.number);
+    Ok(partitions)
+}
+
+/// Follows the chain of extended boot records in an extended partition, each of which
+/// describes a logical partition relative to itself, and links to the next relative to
+/// the start of the extended partition.
+fn read_logical<R: Read + Seek>(
+    disk: &mut R,
+    extended: u64,
+    partitions: &mut Vec<PartitionEntry>,
+) -> io::Result<()> {
+    let mut ebr_start = extended;
+    for number in FIRST_LOGICAL..FIRST_LOGICAL + MAX_LOGICAL {
+        let ebr = read_at(disk, ebr_start * MBR_SECTOR, MBR_SECTOR as usize)?;
+        if ebr.len() < MBR_SECTOR as usize || ebr[510..] != MBR_SIGNATURE {
+            return Err(invalid("invalid extended boot record"));
+        }
+
+        let mut entries = mbr_entries(&ebr);
+        if let Some((kind, start, sectors)) = entries.next().filter(|&(kind, ..)| kind != 0) {
+            partitions.push(mbr_partition(number, kind, ebr_start + start, sectors));
+        }
+
+        match entries.next() {
+            Some((kind, next, _)) if kind != 0 && next != 0 => ebr_start = extended + next,
+            _ => return Ok(()),
+        }
+    }
+
+    Ok(())
+}
+
+/// The type, start sector, and sector count of each of the four entries of an MBR or EBR.
+fn mbr_entries(mbr: &[u8]) -> impl Iterator<Item = (u8, u64, u64)> + '_ {
+    mbr[MBR_ENTRIES..MBR_ENTRIES + 64]
+        .chunks_exact(16)
+        .map(|entry| (entry[4], u64::from(u32_at(entry, 8)), u64::from(u32_at(entry, 12))))
+}
+
+fn mbr_partition(number: u32, kind: u8, start: u64, sectors: u64) -> PartitionEntry {
+    PartitionEntry {
+        number,
+        offset: start * MBR_SECTOR,
+        size: sectors * MBR_SECTOR,
+        partition_type: PartitionType::Mbr(kind),
+        name: None,
+    }
+}
+
+/// Formats a GUID stored in the mixed-endian layout of GPT, in which the first three
+/// fields are little-endian.
+fn format_guid(bytes: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+        u32_at(bytes, 0),
+        u16::from_le_bytes([bytes[4], bytes[5]]),
+        u16::from_le_bytes([bytes[6], bytes[7]]),
+        bytes[8],
+        bytes[9],
+        bytes[10],
+        bytes[11],
+        bytes[12],
+        bytes[13],
+        bytes[14],
+        bytes[15],
+    )
+}
+
+/// Reads up to `len` bytes at `offset`, stopping short at the end of the disk.
+fn read_at<R: Read + Seek>(disk: &mut R, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    disk.seek(SeekFrom::Start(offset))?;
+    let mut buffer = Vec::with_capacity(len);
+    disk.take(len as u64).read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from(u32_at(data, offset)) | u64::from(u32_at(data, offset + 4)) << 32
+}
+
+fn invalid(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, why)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> File {
+        File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)).unwrap()
+    }
+
+    #[test]
+    fn gpt_disk() {
+        let partitions = read(&mut fixture("gpt-disk.img")).unwrap();
+        let summary: Vec<_> = partitions
+            .iter()
+            .map(|p| (p.number, p.offset, p.size, p.name.as_deref(), p.expected_os()))
+            .collect();
+
+        let mib = 1024 * 1024;
+        assert_eq!(
+            summary,
+            [
+                (1, mib, 512 * mib, Some("EFI System Partition"), Some(ExpectedOs::EfiSystem)),
+                (2, 513 * mib, 4096 * mib, Some("root"), Some(ExpectedOs::Linux)),
+                (4, 4609 * mib, 1024 * mib, None, Some(ExpectedOs::Swap)),
+            ]
+        );
+
+        let guid = PartitionType::Gpt("4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709".into());
+        assert_eq!(partitions[1].partition_type, guid);
+        let may_hold_os: Vec<bool> = partitions.iter().map(PartitionEntry::may_hold_os).collect();
+        assert_eq!(may_hold_os, [false, true, false]);
+    }
+
+    #[test]
+    fn mbr_disk_with_logical_partitions() {
+        let partitions = read(&mut fixture("mbr-disk.img")).unwrap();
+        let summary: Vec<_> = partitions
+            .iter()
+            .map(|p| (p.number, p.offset / 512, p.size / 512, p.partition_type.clone()))
+            .collect();
+
+        assert_eq!(
+            summary,
+            [
+                (1, 8, 8, PartitionType::Mbr(0x07)),
+                (5, 17, 23, PartitionType::Mbr(0x83)),
+                (6, 41, 23, PartitionType::Mbr(0x82)),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_partition_table() {
+        assert_eq!(read(&mut io::Cursor::new(vec![0; 4096])).unwrap(), []);
+        assert_eq!(read(&mut io::Cursor::new(Vec::new())).unwrap(), []);
+
+        let mut protective = vec![0; 1024];
+        protective[MBR_ENTRIES + 4] = MBR_PROTECTIVE;
+        protective[510..512].copy_from_slice(&MBR_SIGNATURE);
+        assert!(read(&mut io::Cursor::new(protective)).is_err());
+    }
+
+    #[test]
+    fn implausible_gpt() {
+        let mut disk = Vec::new();
+        fixture("gpt-disk.img").read_to_end(&mut disk).unwrap();
+        let patched = |offset: usize, value: &[u8]| {
+            let mut disk = disk.clone();
+            disk[offset..offset + value.len()].copy_from_slice(value);
+            read(&mut io::Cursor::new(disk))
+        };
+
+        // The entry size, the LBA of the entries, then the last LBA of the first partition.
+        for &entry_size in &[0xFFFF_FFFFu32, 8192, 200, 0] {
+            assert!(patched(512 + 84, &entry_size.to_le_bytes()).is_err(), "{}", entry_size);
+        }
+
+        assert!(patched(512 + 72, &u64::MAX.to_le_bytes()).is_err());
+        assert!(patched(1024 + 40, &u64::MAX.to_le_bytes()).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+//! A minimal parser for the machine type and version resource of Windows PE images, such
+//! as `ntoskrnl.exe`.
+//!
+//! Only the headers, the section table, and the path through the resource directory to the
+//! version resource are read, so that large images don't need to be loaded into memory.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+const DOS_MAGIC: &[u8] = b"MZ";
+const PE_SIGNATURE: &[u8] = b"PE\0\0";
+const PE32_MAGIC: u16 = 0x10B;
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+const COFF_HEADER_LEN: u64 = 20;
+const SECTION_HEADER_LEN: usize = 40;
+/// The index of the resource table among the data directories.
+const RESOURCE_DIRECTORY: u32 = 2;
+/// The resource type of `VS_VERSION_INFO`.
+const RT_VERSION: u32 = 16;
+/// Entries of the resource directory with this bit set lead to another directory.
+const SUBDIRECTORY: u32 = 0x8000_0000;
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+/// Refuse to read resources larger than this, as it would indicate a corrupt image.
+const MAX_RESOURCE: u32 = 64 * 1024;
+
+/// The file version of an image, from the `VS_FIXEDFILEINFO` of its version resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub revision: u16,
+}
+
+/// Reads the `Machine` field of a PE image's COFF header, such as `0x8664` for x86-64.
+pub fn machine<R: Read + Seek>(mut image: R) -> io::Result<u16> {
+    let (_, coff) = coff_header(&mut image)?;
+    u16_at(&coff, 4)
+}
+
+/// Reads the file version of a PE image, if it has a version resource.
+pub fn file_version<R: Read + Seek>(mut image: R) -> io::Result<Option<FileVersion>> {
+    let (pe, coff) = coff_header(&mut image)?;
+    let sections = usize::from(u16_at(&coff, 6)?);
+    let optional_len = usize::from(u16_at(&coff, 20)?);
+    let optional = read_at(&mut image, pe + 4 + COFF_HEADER_LEN, optional_len)?;
+    let directories = match u16_at(&optional, 0)? {
+        PE32_MAGIC => 96,
+        PE32_PLUS_MAGIC => 112,
+        _ => return Err(invalid("unknown optional header magic")),
+    };
+
+    if u32_at(&optional, directories - 4)? <= RESOURCE_DIRECTORY {
+        return Ok(None);
+    }
+
+    let resources_rva = u32_at(&optional, directories + RESOURCE_DIRECTORY as usize * 8)?;
+    if resources_rva == 0 {
+        return Ok(None);
+    }
+
+    let table_offset = pe + 4 + COFF_HEADER_LEN + optional_len as u64;
+    let table = read_at(&mut image, table_offset, sections * SECTION_HEADER_LEN)?;
+    let mut image = Image { reader: image, sections: table };
+    let resources = image.offset(resources_rva)?;
+
+    // Resources are found by type, then by name, then by language.
+    let mut directory = resources;
+    for id in [Some(RT_VERSION), None, None] {
+        let entry = match image.directory_entry(directory, id)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        if entry & SUBDIRECTORY == 0 {
+            let data = image.read(resources + u64::from(entry), 8)?;
+            let (rva, len) = (u32_at(&data, 0)?, u32_at(&data, 4)?);
+            if len > MAX_RESOURCE {
+                return Err(invalid("version resource is too large"));
+            }
+
+            let offset = image.offset(rva)?;
+            return parse_version_info(&image.read(offset, len as usize)?);
+        }
+
+        directory = resources + u64::from(entry & !SUBDIRECTORY);
+    }
+
+    Err(invalid("resource directory is too deep"))
+}
+
+/// Finds the PE signature from the DOS header, and reads it along with the COFF header
+/// which follows it.
+fn coff_header<R: Read + Seek>(image: &mut R) -> io::Result<(u64, Vec<u8>)> {
+    let dos = read_at(image, 0, 0x40)?;
+    if !dos.starts_with(DOS_MAGIC) {
+        return Err(invalid("missing MZ signature"));
+    }
+
+    let pe = u64::from(u32_at(&dos, 0x3C)?);
+    let coff = read_at(image, pe, 4 + COFF_HEADER_LEN as usize)?;
+    if !coff.starts_with(PE_SIGNATURE) {
+        return Err(invalid("missing PE signature"));
+    }
+
+    Ok((pe, coff))
+}
+
+/// Parses the `VS_FIXEDFILEINFO` from a `VS_VERSION_INFO` resource.
+pub fn parse_version_info(resource: &[u8]) -> io::Result<Option<FileVersion>> {
+    let key: Vec<u16> = "VS_VERSION_INFO\0".encode_utf16().collect();
+    let key_len = key.len() * 2;
+    let name = slice(resource, 6, key_len)?;
+    if name.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).ne(key) {
+        return Err(invalid("missing VS_VERSION_INFO key"));
+    }
+
+    if u16_at(resource, 2)? == 0 {
+        return Ok(None);
+    }
+
+    // The fixed file info is aligned to four bytes.
+    let info = (6 + key_len + 3) & !3;
+    if u32_at(resource, info)? != FIXED_FILE_INFO_SIGNATURE {
+        return Err(invalid("missing VS_FIXEDFILEINFO signature"));
+    }
+
+    let (most, least) = (u32_at(resource, info + 8)?, u32_at(resource, info + 12)?);
+    Ok(Some(FileVersion {
+        major: (most >> 16) as u16,
+        minor: most as u16,
+        build: (least >> 16) as u16,
+        revision: least as u16,
+    }))
+}
+
+/// A PE image, whose sections map relative virtual addresses to offsets in the file.
+struct Image<R> {
+    reader: R,
+    sections: Vec<u8>,
+}
+
+impl<R: Read + Seek> Image<R> {
+    fn offset(&self, rva: u32) -> io::Result<u64> {
+        for section in self.sections.chunks_exact(SECTION_HEADER_LEN) {
+            let (size, address) = (u32_at(section, 8)?, u32_at(section, 12)?);
+            let raw = u32_at(section, 20)?;
+            if rva >= address && rva - address < size {
+                return Ok(u64::from(raw) + u64::from(rva - address));
+            }
+        }
+
+        Err(invalid("address is outside of every section"))
+    }
+
+    /// Finds the entry of a resource directory with the given ID, or else its first.
+    fn directory_entry(&mut self, directory: u64, id: Option<u32>) -> io::Result<Option<u32>> {
+        let header = self.read(directory, 16)?;
+        let named = u64::from(u16_at(&header, 12)?);
+        let ids = usize::from(u16_at(&header, 14)?);
+
+        // Entries with IDs follow those with names.
+        let entries = self.read(directory + 16 + named * 8, ids * 8)?;
+        for entry in entries.chunks_exact(8) {
+            if id.is_none() || id == Some(u32_at(entry, 0)?) {
+                return u32_at(entry, 4).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn read(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        read_at(&mut self.reader, offset, len)
+    }
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0; len];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn slice(buf: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    buf.get(offset..offset + len).ok_or_else(|| invalid("truncated structure"))
+}
+
+fn u16_at(buf: &[u8], offset: usize) -> io::Result<u16> {
+    slice(buf, offset, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> io::Result<u32> {
+    slice(buf, offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn invalid(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid PE image: {}", why))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::Path;
+
+    /// The `VS_VERSION_INFO` resource of a Windows 10 2004 kernel.
+    pub fn version_resource() -> Vec<u8> {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ntoskrnl-version.bin");
+        fs::read(path).unwrap()
+    }
+
+    /// Builds a 64-bit PE image with a single `.rsrc` section holding the given version
+    /// resource.
+    pub fn image(resource: &[u8]) -> Vec<u8> {
+        const SECTION_RVA: u32 = 0x1000;
+        const SECTION_OFFSET: usize = 0x200;
+
+        let mut image = vec![0; SECTION_OFFSET];
+        image[..2].copy_from_slice(DOS_MAGIC);
+        image[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+
+        let mut pe = Vec::new();
+        pe.extend_from_slice(PE_SIGNATURE);
+        pe.extend_from_slice(&[0x64, 0x86, 1, 0]);
+        pe.extend_from_slice(&[0; 12]);
+        pe.extend_from_slice(&240u16.to_le_bytes());
+        pe.extend_from_slice(&[0x22, 0]);
+
+        let mut optional = vec![0; 240];
+        optional[..2].copy_from_slice(&PE32_PLUS_MAGIC.to_le_bytes());
+        optional[108..112].copy_from_slice(&16u32.to_le_bytes());
+        optional[112 + 16..112 + 20].copy_from_slice(&SECTION_RVA.to_le_bytes());
+        pe.extend(optional);
+
+        // The type, name, and language directories, the data entry, then the resource.
+        let mut rsrc = Vec::new();
+        for (level, id) in [RT_VERSION, 1, 0x409].iter().enumerate() {
+            let next = (level as u32 + 1) * 24;
+            let next = if level < 2 { next | SUBDIRECTORY } else { next };
+            rsrc.extend_from_slice(&[0; 14]);
+            rsrc.extend_from_slice(&1u16.to_le_bytes());
+            rsrc.extend_from_slice(&id.to_le_bytes());
+            rsrc.extend_from_slice(&next.to_le_bytes());
+        }
+
+        rsrc.extend_from_slice(&(SECTION_RVA + 88).to_le_bytes());
+        rsrc.extend_from_slice(&(resource.len() as u32).to_le_bytes());
+        rsrc.extend_from_slice(&[0; 8]);
+        rsrc.extend_from_slice(resource);
+
+        let mut section = vec![0; SECTION_HEADER_LEN];
+        section[..5].copy_from_slice(b".rsrc");
+        section[8..12].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        section[12..16].copy_from_slice(&SECTION_RVA.to_le_bytes());
+        section[16..20].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        section[20..24].copy_from_slice(&(SECTION_OFFSET as u32).to_le_bytes());
+        pe.extend(section);
+
+        image[0x40..0x40 + pe.len()].copy_from_slice(&pe);
+        image.extend(rsrc);
+        image
+    }
+
+    const WINDOWS_2004: FileVersion =
+        FileVersion { major: 10, minor: 0, build: 19041, revision: 1 };
+
+    #[test]
+    fn version_resource_fixture() {
+        assert_eq!(parse_version_info(&version_resource()).unwrap(), Some(WINDOWS_2004));
+        assert!(parse_version_info(&version_resource()[..40]).is_err());
+        assert!(parse_version_info(b"not a version resource, but long enough").is_err());
+    }
+
+    #[test]
+    fn image_version() {
+        let image = image(&version_resource());
+        assert_eq!(file_version(Cursor::new(&image)).unwrap(), Some(WINDOWS_2004));
+        assert!(file_version(Cursor::new(&image[..0x100])).is_err());
+        assert!(file_version(Cursor::new(b"\x7fELF".to_vec())).is_err());
+    }
+
+    #[test]
+    fn image_machine() {
+        let mut image = image(&version_resource());
+        assert_eq!(machine(Cursor::new(&image)).unwrap(), 0x8664);
+        image[0x44..0x46].copy_from_slice(&0xAA64u16.to_le_bytes());
+        assert_eq!(machine(Cursor::new(&image)).unwrap(), 0xAA64);
+        assert!(machine(Cursor::new(b"\x7fELF".to_vec())).is_err());
+    }
+
+    #[test]
+    fn cyclic_resource_directory() {
+        // Points the entry of the language directory back at the type directory.
+        let mut image = image(&version_resource());
+        let entry = 0x200 + 2 * 24 + 20;
+        image[entry..entry + 4].copy_from_slice(&SUBDIRECTORY.to_le_bytes());
+        let why = file_version(Cursor::new(&image)).unwrap_err();
+        assert!(why.to_string().contains("too deep"), "{}", why);
+    }
+}
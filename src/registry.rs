@@ -0,0 +1,375 @@
+//! A minimal, read-only parser for Windows registry hive files.
+//!
+//! Only as much of the format is understood as is needed to look up the values of a key
+//! by its path. Cells are read on demand, so that large hives such as `SOFTWARE` don't
+//! need to be loaded into memory.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Cell offsets are relative to the first hive bin, which follows the 4 KiB base block.
+const HBIN_START: u64 = 0x1000;
+/// Refuse to read cells larger than this, as it would indicate a corrupt hive.
+const MAX_CELL: usize = 1024 * 1024;
+
+/// Key names are stored as Latin-1 rather than UTF-16LE.
+const KEY_COMP_NAME: u16 = 0x0020;
+/// Value names are stored as Latin-1 rather than UTF-16LE.
+const VALUE_COMP_NAME: u16 = 0x0001;
+/// Value data of four bytes or less is stored in place of the data offset.
+const DATA_INLINE: u32 = 0x8000_0000;
+
+const REG_SZ: u32 = 1;
+const REG_EXPAND_SZ: u32 = 2;
+const REG_DWORD: u32 = 4;
+
+/// A reference to a key node within a hive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key(u32);
+
+/// A registry hive, read from the underlying reader.
+pub struct Hive<R> {
+    reader: R,
+    root: Key,
+}
+
+impl<R: Read + Seek> Hive<R> {
+    /// Validates the base block of the hive, and locates its root key.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut base = [0u8; 0x28];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut base)?;
+        if &base[..4] != b"regf" {
+            return Err(invalid("missing regf signature"));
+        }
+
+        let root = Key(u32_at(&base, 0x24)?);
+        Ok(Hive { reader, root })
+    }
+
+    /// Finds the key at the given path, relative to the root key.
+    ///
+    /// Key names are compared case-insensitively, as Windows does.
+    pub fn key(&mut self, path: &[&str]) -> io::Result<Option<Key>> {
+        let mut key = self.root;
+        for name in path {
+            match self.subkey(key, name)? {
+                Some(subkey) => key = subkey,
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(key))
+    }
+
+    /// Reads the named value of a key as a string.
+    ///
+    /// `REG_DWORD` values are formatted as decimal numbers, and other non-string
+    /// types are ignored.
+    pub fn value(&mut self, key: Key, name: &str) -> io::Result<Option<String>> {
+        let nk = self.key_node(key)?;
+        let count = u32_at(&nk, 0x24)? as usize;
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let list = self.cell(u32_at(&nk, 0x28)?)?;
+        for index in 0..count {
+            let vk = self.cell(u32_at(&list, index * 4)?)?;
+            if vk.get(..2) != Some(b"vk") {
+                return Err(invalid("expected a vk cell"));
+            }
+
+            let name_len = u16_at(&vk, 0x02)? as usize;
+            let flags = u16_at(&vk, 0x10)?;
+            let value_name = decode_name(slice(&vk, 0x14, name_len)?, flags & VALUE_COMP_NAME != 0);
+            if !value_name.eq_ignore_ascii_case(name) {
+                continue;
+            }
+
+            let size = u32_at(&vk, 0x04)?;
+            let offset = u32_at(&vk, 0x08)?;
+            let data = if size & DATA_INLINE != 0 {
+                let size = (size & !DATA_INLINE) as usize;
+                offset.to_le_bytes()[..size.min(4)].to_vec()
+            } else {
+                let mut data = self.cell(offset)?;
+                data.truncate(size as usize);
+                data
+            };
+
+            return Ok(match u32_at(&vk, 0x0C)? {
+                REG_SZ | REG_EXPAND_SZ => Some(decode_utf16(&data)),
+                REG_DWORD => Some(u32_at(&data, 0)?.to_string()),
+                _ => None,
+            });
+        }
+
+        Ok(None)
+    }
+
+    fn subkey(&mut self, key: Key, name: &str) -> io::Result<Option<Key>> {
+        let nk = self.key_node(key)?;
+        if u32_at(&nk, 0x14)? == 0 {
+            return Ok(None);
+        }
+
+        self.search_list(u32_at(&nk, 0x1C)?, name, false)
+    }
+
+    /// Searches a subkey list for a key with the given name. An `ri` list indexes other
+    /// lists, but never another `ri`, so a nested one is refused rather than followed.
+    fn search_list(&mut self, offset: u32, name: &str, nested: bool) -> io::Result<Option<Key>> {
+        let list = self.cell(offset)?;
+        let count = u16_at(&list, 0x02)? as usize;
+        let (stride, indirect) = match list.get(..2) {
+            Some(b"lf") | Some(b"lh") => (8, false),
+            Some(b"li") => (4, false),
+            Some(b"ri") if nested => return Err(invalid("nested ri subkey list")),
+            Some(b"ri") => (4, true),
+            _ => return Err(invalid("unknown subkey list type")),
+        };
+
+        for index in 0..count {
+            let entry = u32_at(&list, 4 + index * stride)?;
+            if indirect {
+                if let Some(key) = self.search_list(entry, name, true)? {
+                    return Ok(Some(key));
+                }
+            } else if self.key_name(Key(entry))?.eq_ignore_ascii_case(name) {
+                return Ok(Some(Key(entry)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn key_name(&mut self, key: Key) -> io::Result<String> {
+        let nk = self.key_node(key)?;
+        let flags = u16_at(&nk, 0x02)?;
+        let len = u16_at(&nk, 0x48)? as usize;
+        Ok(decode_name(slice(&nk, 0x4C, len)?, flags & KEY_COMP_NAME != 0))
+    }
+
+    fn key_node(&mut self, key: Key) -> io::Result<Vec<u8>> {
+        let nk = self.cell(key.0)?;
+        if nk.get(..2) != Some(b"nk") {
+            return Err(invalid("expected an nk cell"));
+        }
+
+        Ok(nk)
+    }
+
+    /// Reads the data of the allocated cell at the given offset.
+    fn cell(&mut self, offset: u32) -> io::Result<Vec<u8>> {
+        self.reader.seek(SeekFrom::Start(HBIN_START + u64::from(offset)))?;
+        let mut size = [0u8; 4];
+        self.reader.read_exact(&mut size)?;
+
+        // Allocated cells have a negative size, which includes the size field itself.
+        let size = i32::from_le_bytes(size);
+        if size >= 0 {
+            return Err(invalid("referenced cell is not allocated"));
+        }
+
+        let len = (size.unsigned_abs() as usize).saturating_sub(4);
+        if len > MAX_CELL {
+            return Err(invalid("cell is too large"));
+        }
+
+        let mut data = vec![0u8; len];
+        self.reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+fn decode_name(bytes: &[u8], latin1: bool) -> String {
+    if latin1 {
+        bytes.iter().map(|&b| b as char).collect()
+    } else {
+        decode_utf16(bytes)
+    }
+}
+
+/// Decodes a UTF-16LE string, stopping at the first null terminator.
+fn decode_utf16(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0);
+    ::std::char::decode_utf16(units)
+        .map(|c| c.unwrap_or(::std::char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn slice(buf: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    buf.get(offset..offset + len).ok_or_else(|| invalid("cell is truncated"))
+}
+
+fn u16_at(buf: &[u8], offset: usize) -> io::Result<u16> {
+    slice(buf, offset, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> io::Result<u32> {
+    slice(buf, offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn invalid(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid registry hive: {}", why))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Assembles cells into a hive, with offsets relative to the first hive bin.
+    struct HiveBuilder {
+        bin: Vec<u8>,
+    }
+
+    impl HiveBuilder {
+        fn new() -> Self {
+            let mut bin = b"hbin".to_vec();
+            bin.resize(0x20, 0);
+            HiveBuilder { bin }
+        }
+
+        fn cell(&mut self, data: &[u8]) -> u32 {
+            let offset = self.bin.len() as u32;
+            let size = (data.len() + 4 + 7) & !7;
+            self.bin.extend_from_slice(&(-(size as i32)).to_le_bytes());
+            self.bin.extend_from_slice(data);
+            self.bin.resize(offset as usize + size, 0);
+            offset
+        }
+
+        fn string(&mut self, name: &str, value: &str) -> u32 {
+            let data: Vec<u8> = value
+                .encode_utf16()
+                .chain(Some(0))
+                .flat_map(|unit| unit.to_le_bytes().to_vec())
+                .collect();
+            let data_offset = self.cell(&data);
+            self.value(name, data.len() as u32, data_offset, REG_SZ)
+        }
+
+        fn dword(&mut self, name: &str, value: u32) -> u32 {
+            self.value(name, 4 | DATA_INLINE, value, REG_DWORD)
+        }
+
+        fn value(&mut self, name: &str, size: u32, offset: u32, kind: u32) -> u32 {
+            let mut vk = b"vk".to_vec();
+            vk.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            vk.extend_from_slice(&size.to_le_bytes());
+            vk.extend_from_slice(&offset.to_le_bytes());
+            vk.extend_from_slice(&kind.to_le_bytes());
+            vk.extend_from_slice(&VALUE_COMP_NAME.to_le_bytes());
+            vk.extend_from_slice(&[0, 0]);
+            vk.extend_from_slice(name.as_bytes());
+            self.cell(&vk)
+        }
+
+        fn key(&mut self, name: &str, subkeys: &[u32], values: &[u32]) -> u32 {
+            let mut nk = vec![0u8; 0x4C];
+            nk[..2].copy_from_slice(b"nk");
+            nk[0x02..0x04].copy_from_slice(&KEY_COMP_NAME.to_le_bytes());
+            if !subkeys.is_empty() {
+                let mut lf = b"lf".to_vec();
+                lf.extend_from_slice(&(subkeys.len() as u16).to_le_bytes());
+                for &subkey in subkeys {
+                    lf.extend_from_slice(&subkey.to_le_bytes());
+                    lf.extend_from_slice(&[0; 4]);
+                }
+                let list = self.cell(&lf);
+                nk[0x14..0x18].copy_from_slice(&(subkeys.len() as u32).to_le_bytes());
+                nk[0x1C..0x20].copy_from_slice(&list.to_le_bytes());
+            }
+            if !values.is_empty() {
+                let list: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+                let list = self.cell(&list);
+                nk[0x24..0x28].copy_from_slice(&(values.len() as u32).to_le_bytes());
+                nk[0x28..0x2C].copy_from_slice(&list.to_le_bytes());
+            }
+            nk[0x48..0x4A].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            nk.extend_from_slice(name.as_bytes());
+            self.cell(&nk)
+        }
+
+        fn finish(self, root: u32) -> Vec<u8> {
+            let mut hive = b"regf".to_vec();
+            hive.resize(HBIN_START as usize, 0);
+            hive[0x24..0x28].copy_from_slice(&root.to_le_bytes());
+            hive.extend_from_slice(&self.bin);
+            hive
+        }
+    }
+
+    /// Builds a `SOFTWARE` hive with the given string values under
+    /// `Microsoft\Windows NT\CurrentVersion`.
+    pub fn software_hive(values: &[(&str, &str)]) -> Vec<u8> {
+        let mut hive = HiveBuilder::new();
+        let values: Vec<u32> =
+            values.iter().map(|&(name, value)| hive.string(name, value)).collect();
+        let current = hive.key("CurrentVersion", &[], &values);
+        let unrelated = hive.key("Current", &[], &[]);
+        let nt = hive.key("Windows NT", &[unrelated, current], &[]);
+        let microsoft = hive.key("Microsoft", &[nt], &[]);
+        let root = hive.key("ROOT", &[microsoft], &[]);
+        hive.finish(root)
+    }
+
+    #[test]
+    fn hive_string_values() {
+        let data = software_hive(&[("ProductName", "Windows 10 Pro"), ("CurrentBuild", "19045")]);
+        let mut hive = Hive::new(Cursor::new(data)).unwrap();
+        let key = hive.key(&["microsoft", "WINDOWS NT", "CurrentVersion"]).unwrap().unwrap();
+        assert_eq!(hive.value(key, "productname").unwrap(), Some("Windows 10 Pro".into()));
+        assert_eq!(hive.value(key, "CurrentBuild").unwrap(), Some("19045".into()));
+        assert_eq!(hive.value(key, "DisplayVersion").unwrap(), None);
+        assert_eq!(hive.key(&["Microsoft", "Windows"]).unwrap(), None);
+    }
+
+    #[test]
+    fn hive_dword_values() {
+        let mut builder = HiveBuilder::new();
+        let major = builder.dword("CurrentMajorVersionNumber", 10);
+        let root = builder.key("ROOT", &[], &[major]);
+        let mut hive = Hive::new(Cursor::new(builder.finish(root))).unwrap();
+        let key = hive.key(&[]).unwrap().unwrap();
+        assert_eq!(hive.value(key, "CurrentMajorVersionNumber").unwrap(), Some("10".into()));
+    }
+
+    #[test]
+    fn hive_indexed_subkeys() {
+        // An `ri` list naming the `lf` list of a key, and then itself.
+        let mut builder = HiveBuilder::new();
+        let child = builder.key("Child", &[], &[]);
+        let lf = builder.key("Parent", &[child], &[]) + 4 + 0x1C;
+        let lf = u32_at(&builder.bin[lf as usize..], 0).unwrap();
+        let ri = builder.bin.len() as u32;
+        let mut list = b"ri".to_vec();
+        list.extend_from_slice(&2u16.to_le_bytes());
+        list.extend_from_slice(&lf.to_le_bytes());
+        list.extend_from_slice(&ri.to_le_bytes());
+        assert_eq!(builder.cell(&list), ri);
+
+        let root = builder.key("ROOT", &[], &[]);
+        let mut data = builder.finish(root);
+        let nk = HBIN_START as usize + root as usize + 4;
+        data[nk + 0x14..nk + 0x18].copy_from_slice(&1u32.to_le_bytes());
+        data[nk + 0x1C..nk + 0x20].copy_from_slice(&ri.to_le_bytes());
+
+        let mut hive = Hive::new(Cursor::new(data)).unwrap();
+        assert!(hive.key(&["Child"]).unwrap().is_some());
+        assert!(hive.key(&["Missing"]).is_err());
+    }
+
+    #[test]
+    fn hive_invalid() {
+        assert!(Hive::new(Cursor::new(b"not a hive at all, really".to_vec())).is_err());
+        let mut data = software_hive(&[]);
+        data.truncate(HBIN_START as usize + 0x30);
+        let mut hive = Hive::new(Cursor::new(data)).unwrap();
+        assert!(hive.key(&["Microsoft"]).is_err());
+    }
+}
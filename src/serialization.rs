@@ -0,0 +1,75 @@
+//! Serde support for the foreign types held by `OS`, enabled by the `serde` feature.
+//!
+//! Neither `OsRelease` nor `PartitionID` implement the serde traits, so their fields are
+//! mirrored here with serde's remote derive.
+
+use os_release::OsRelease;
+use partition_identity::{PartitionID, PartitionSource};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "OsRelease")]
+pub(crate) struct OsReleaseDef {
+    pub bug_report_url: String,
+    pub home_url: String,
+    pub id_like: String,
+    pub id: String,
+    pub name: String,
+    pub pretty_name: String,
+    pub privacy_policy_url: String,
+    pub support_url: String,
+    pub version_codename: String,
+    pub version_id: String,
+    pub version: String,
+    pub extra: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "PartitionSource")]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum PartitionSourceDef {
+    ID,
+    Label,
+    PartLabel,
+    PartUUID,
+    Path,
+    UUID,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "PartitionID")]
+pub(crate) struct PartitionIDDef {
+    #[serde(with = "PartitionSourceDef")]
+    pub variant: PartitionSource,
+    pub id: String,
+}
+
+/// Remote definitions can't be applied through an `Option` or `Vec`, so this wrapper is
+/// used to (de)serialize the elements of those.
+#[derive(Serialize, Deserialize)]
+struct Id(#[serde(with = "PartitionIDDef")] PartitionID);
+
+pub(crate) mod option_partition_id {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(id: &Option<PartitionID>, s: S) -> Result<S::Ok, S::Error> {
+        id.clone().map(Id).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<PartitionID>, D::Error> {
+        Option::<Id>::deserialize(d).map(|id| id.map(|Id(id)| id))
+    }
+}
+
+pub(crate) mod vec_partition_id {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(ids: &[PartitionID], s: S) -> Result<S::Ok, S::Error> {
+        ids.iter().cloned().map(Id).collect::<Vec<_>>().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<PartitionID>, D::Error> {
+        Vec::<Id>::deserialize(d).map(|ids| ids.into_iter().map(|Id(id)| id).collect())
+    }
+}
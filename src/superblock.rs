@@ -0,0 +1,179 @@
+//! Identifies the filesystem of a device from the magic numbers in its superblock.
+//!
+//! Only the signatures of filesystems an OS is likely to be installed to are known, and
+//! nothing beyond the signature is validated.
+
+use std::io::{self, Read};
+
+/// Enough of the device to reach the furthest signature, that of btrfs.
+pub const PROBE_LEN: u64 = 0x10048;
+
+/// The ext superblock starts 1 KiB into the device.
+const EXT_SUPERBLOCK: usize = 0x400;
+const EXT_MAGIC: u16 = 0xEF53;
+const EXT_COMPAT_HAS_JOURNAL: u32 = 0x0004;
+/// Incompatible features that ext2 and ext3 lack: extents, 64-bit, and flexible block groups.
+const EXT4_INCOMPAT: u32 = 0x0040 | 0x0080 | 0x0200;
+/// The journal has transactions which have yet to be replayed.
+const EXT_INCOMPAT_RECOVER: u32 = 0x0004;
+/// The state flags of a filesystem which was unmounted cleanly, and of one with errors.
+const EXT_VALID_FS: u16 = 0x0001;
+const EXT_ERROR_FS: u16 = 0x0002;
+/// Enough of the device to hold the ext superblock.
+pub const EXT_PROBE_LEN: u64 = (EXT_SUPERBLOCK + 0x64) as u64;
+
+/// Reads the start of a device, and identifies its filesystem.
+pub fn probe<R: Read>(reader: R) -> io::Result<Option<&'static str>> {
+    let mut buffer = Vec::with_capacity(PROBE_LEN as usize);
+    reader.take(PROBE_LEN).read_to_end(&mut buffer)?;
+    Ok(identify(&buffer))
+}
+
+/// Identifies a filesystem by the signatures at the start of a device, with the name that
+/// it's mounted by.
+pub fn identify(data: &[u8]) -> Option<&'static str> {
+    let has = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+
+    if let Some(ext) = ext_version(data) {
+        Some(ext)
+    } else if has(0x10040, b"_BHRfS_M") {
+        Some("btrfs")
+    } else if has(0, b"XFSB") {
+        Some("xfs")
+    } else if has(3, b"NTFS    ") {
+        Some("ntfs")
+    } else if has(3, b"EXFAT   ") {
+        Some("exfat")
+    } else if has(510, &[0x55, 0xAA]) && (has(0x52, b"FAT32   ") || has(0x36, b"FAT1")) {
+        Some("vfat")
+    } else if has(0x400, b"H+") || has(0x400, b"HX") {
+        Some("hfsplus")
+    } else if has(0x20, b"NXSB") {
+        Some("apfs")
+    } else if has(0x8001, b"CD001") {
+        Some("iso9660")
+    } else if has(4086, b"SWAPSPACE2") {
+        Some("swap")
+    } else {
+        None
+    }
+}
+
+/// Whether an ext filesystem needs to be checked before it's used read-write: it wasn't
+/// unmounted cleanly, errors were found in it, or its journal needs to be replayed.
+///
+/// `None` is returned if the data doesn't hold an ext superblock.
+pub fn ext_needs_fsck(data: &[u8]) -> Option<bool> {
+    ext_version(data)?;
+    let superblock = &data[EXT_SUPERBLOCK..];
+    let state = u16::from_le_bytes([superblock[0x3A], superblock[0x3B]]);
+    let journaled = u32_at(superblock, 0x5C) & EXT_COMPAT_HAS_JOURNAL != 0;
+    let recover = u32_at(superblock, 0x60) & EXT_INCOMPAT_RECOVER != 0;
+    Some(state & EXT_VALID_FS == 0 || state & EXT_ERROR_FS != 0 || (journaled && recover))
+}
+
+/// Tells ext2, ext3, and ext4 apart by their feature flags.
+fn ext_version(data: &[u8]) -> Option<&'static str> {
+    let superblock = data.get(EXT_SUPERBLOCK..EXT_SUPERBLOCK + 0x64)?;
+    if u16::from_le_bytes([superblock[0x38], superblock[0x39]]) != EXT_MAGIC {
+        None
+    } else if u32_at(superblock, 0x60) & EXT4_INCOMPAT != 0 {
+        Some("ext4")
+    } else if u32_at(superblock, 0x5C) & EXT_COMPAT_HAS_JOURNAL != 0 {
+        Some("ext3")
+    } else {
+        Some("ext2")
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Bytes to write at an offset of a device.
+    type Patch<'a> = (usize, &'a [u8]);
+
+    /// A zeroed device with the given bytes written at their offsets.
+    fn device(patches: &[Patch]) -> Vec<u8> {
+        let mut data = vec![0; PROBE_LEN as usize];
+        for &(offset, bytes) in patches {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        data
+    }
+
+    #[test]
+    fn ext_versions() {
+        let magic: &[u8] = &[0x53, 0xEF];
+        assert_eq!(identify(&device(&[(0x438, magic)])), Some("ext2"));
+        assert_eq!(identify(&device(&[(0x438, magic), (0x45C, &[0x04, 0, 0, 0])])), Some("ext3"));
+
+        let ext4 =
+            device(&[(0x438, magic), (0x45C, &[0x3C, 0, 0, 0]), (0x460, &[0xC2, 0x02, 0, 0])]);
+        assert_eq!(identify(&ext4), Some("ext4"));
+    }
+
+    #[test]
+    fn signatures() {
+        let fat: &[Patch] = &[(3, b"mkfs.fat"), (0x52, b"FAT32   "), (510, &[0x55, 0xAA])];
+        let cases: &[(&[Patch], &str)] = &[
+            (&[(0x10040, b"_BHRfS_M")], "btrfs"),
+            (&[(0, b"XFSB")], "xfs"),
+            (&[(3, b"NTFS    "), (510, &[0x55, 0xAA])], "ntfs"),
+            (&[(3, b"EXFAT   ")], "exfat"),
+            (fat, "vfat"),
+            (&[(0x36, b"FAT16   "), (510, &[0x55, 0xAA])], "vfat"),
+            (&[(0x400, b"H+")], "hfsplus"),
+            (&[(0x20, b"NXSB")], "apfs"),
+            (&[(0x8001, b"CD001")], "iso9660"),
+            (&[(4086, b"SWAPSPACE2")], "swap"),
+        ];
+
+        for &(patches, fs) in cases {
+            assert_eq!(identify(&device(patches)), Some(fs));
+        }
+    }
+
+    #[test]
+    fn ext_fsck_state() {
+        let magic: Patch = (0x438, &[0x53, 0xEF]);
+        let clean: Patch = (0x43A, &[0x01, 0]);
+        let has_journal: Patch = (0x45C, &[0x04, 0, 0, 0]);
+        let ext4_features = [0xC2, 0x02, 0, 0];
+        let needs_recovery = [0xC6, 0x02, 0, 0];
+
+        let cases: &[(&[Patch], Option<bool>)] = &[
+            (&[magic, clean, has_journal, (0x460, &ext4_features)], Some(false)),
+            (&[magic, clean, has_journal, (0x460, &needs_recovery)], Some(true)),
+            // Not cleanly unmounted, and cleanly unmounted but with errors.
+            (&[magic, has_journal, (0x460, &ext4_features)], Some(true)),
+            (&[magic, (0x43A, &[0x03, 0]), has_journal, (0x460, &ext4_features)], Some(true)),
+            // The recovery flag means nothing without a journal.
+            (&[magic, clean, (0x460, &needs_recovery)], Some(false)),
+            (&[clean, has_journal], None),
+        ];
+
+        for &(patches, needs_fsck) in cases {
+            assert_eq!(ext_needs_fsck(&device(patches)), needs_fsck, "{:?}", patches);
+        }
+
+        assert_eq!(ext_needs_fsck(&device(&[magic, clean])[..0x400]), None);
+    }
+
+    #[test]
+    fn unknown_or_short() {
+        assert_eq!(identify(&device(&[])), None);
+        // FAT labels without the boot sector signature.
+        assert_eq!(identify(&device(&[(0x52, b"FAT32   ")])), None);
+        // Too short to hold a btrfs superblock, but ext is still found.
+        assert_eq!(identify(&device(&[(0x438, &[0x53, 0xEF])])[..0x1000]), Some("ext2"));
+        assert_eq!(probe(Cursor::new(&[0u8; 16][..])).unwrap(), None);
+        assert_eq!(probe(Cursor::new(device(&[(0, b"XFSB")]))).unwrap(), Some("xfs"));
+    }
+}
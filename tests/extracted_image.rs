@@ -0,0 +1,104 @@
+extern crate os_detect;
+extern crate partition_identity;
+
+use os_detect::{
+    detect_linux, detect_os_from_path, detect_os_from_path_all, detect_os_summary,
+    try_detect_linux, DetectError, LinuxInstall, OsKind, OS,
+};
+use partition_identity::PartitionID;
+use std::path::{Path, PathBuf};
+
+/// A tree of files as they would be extracted from a dual-boot disk image.
+fn dual_boot() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/dual-boot")
+}
+
+/// An install whose `/etc/os-release` links to a `/usr/lib/os-release` that's missing.
+fn broken_os_release() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/broken-os-release")
+}
+
+/// The dom0 of a Qubes OS install, whose os-release is that of Fedora.
+fn qubes_dom0() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/qubes-dom0")
+}
+
+/// A ChromeOS Flex install, which has an os-release as well as its lsb-release.
+fn chromeos_flex() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/chromeos-flex")
+}
+
+#[test]
+fn detects_linux_in_extracted_tree() {
+    match detect_os_from_path(&dual_boot()) {
+        Some(OS::Linux(LinuxInstall {
+            info,
+            efi,
+            kernels,
+            locale,
+            root,
+            swapfile,
+            grub_default,
+            ..
+        })) => {
+            assert_eq!(info.pretty_name, "Ubuntu 22.04.3 LTS");
+            assert_eq!(efi, Some(PartitionID::new_uuid("0C5A-1B2F".into())));
+            assert_eq!(kernels, ["6.2.0-39-generic"]);
+            assert_eq!(locale.as_deref(), Some("en_GB.UTF-8"));
+            assert_eq!(root, None);
+            assert_eq!(swapfile.as_deref(), Some("/swapfile"));
+            assert_eq!(grub_default.as_deref(), Some("Ubuntu"));
+        }
+        other => panic!("expected Linux, found {:?}", other),
+    }
+}
+
+#[test]
+fn detects_every_os_in_extracted_tree() {
+    let found = detect_os_from_path_all(&dual_boot());
+    let names: Vec<&str> = found.iter().map(OS::name).collect();
+    assert_eq!(names, ["Ubuntu", "Windows"]);
+
+    match found.last() {
+        Some(&OS::Windows { pagefile, .. }) => assert!(pagefile),
+        other => panic!("expected Windows, found {:?}", other),
+    }
+}
+
+#[test]
+fn reports_dangling_os_release() {
+    match try_detect_linux(&broken_os_release()) {
+        Err(DetectError::OsRelease { path, why }) => {
+            assert_eq!(path, broken_os_release().join("etc/os-release"));
+            assert_eq!(why.to_string(), "dangling symlink to ../usr/lib/os-release");
+        }
+        other => panic!("expected an os-release error, found {:?}", other),
+    }
+
+    // Without an os-release to identify it, the install is only known by its remains.
+    match detect_os_from_path(&broken_os_release()) {
+        Some(OS::Unknown { hints }) => assert_eq!(hints, ["etc/", "etc/os-release"]),
+        other => panic!("expected an unknown OS, found {:?}", other),
+    }
+}
+
+#[test]
+fn refines_fedora_to_qubes() {
+    let os = detect_os_from_path(&qubes_dom0()).unwrap();
+    assert_eq!((os.name(), os.version().as_deref()), ("Qubes OS", Some("4.1.2")));
+    assert_eq!(os.to_string(), "Qubes OS 4.1.2 (R4.1)");
+    assert!(os.is_like("fedora"));
+}
+
+#[test]
+fn detects_chromeos_despite_os_release() {
+    assert_eq!(detect_linux(&chromeos_flex()), None);
+    assert_eq!(
+        detect_os_from_path(&chromeos_flex()),
+        Some(OS::ChromeOs("Chrome OS 15437.61.0 (Official Build) stable-channel reven".into()))
+    );
+    assert_eq!(
+        detect_os_summary(&chromeos_flex()).map(|summary| summary.kind),
+        Some(OsKind::ChromeOs)
+    );
+}